@@ -0,0 +1,434 @@
+/*-
+ * SPDX-License-Identifier: BSD-2-Clause
+ *
+ * BSD 2-Clause License
+ *
+ * Copyright (c) 2021, Marcelo Araujo <araujobsdport@gmail.com>
+ * All rights reserved.
+ *
+ * Redistribution and use in source and binary forms, with or without
+ * modification, are permitted provided that the following conditions are met:
+ *
+ * 1. Redistributions of source code must retain the above copyright notice, this
+ *    list of conditions and the following disclaimer.
+ *
+ * 2. Redistributions in binary form must reproduce the above copyright notice,
+ *    this list of conditions and the following disclaimer in the documentation
+ *    and/or other materials provided with the distribution.
+ *
+ * THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS"
+ * AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE
+ * IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE ARE
+ * DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT HOLDER OR CONTRIBUTORS BE LIABLE
+ * FOR ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL, EXEMPLARY, OR CONSEQUENTIAL
+ * DAMAGES (INCLUDING, BUT NOT LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR
+ * SERVICES; LOSS OF USE, DATA, OR PROFITS; OR BUSINESS INTERRUPTION) HOWEVER
+ * CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY,
+ * OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
+ * OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+ */
+
+//! The programs a scan can be delegated to.
+
+use crate::{
+    decode_ssid, default_wireless_interface, frequency_to_channel, parse_signal, percent_to_dbm,
+    split_terse, Error, Network, Security,
+};
+use std::env;
+use std::io;
+use std::path::PathBuf;
+use std::process::{Command, Output};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static VERBOSE: AtomicBool = AtomicBool::new(false);
+
+/// Echo every command `run` spawns and its raw output to stderr, to tell
+/// parsing bugs from backend ones.
+pub fn set_verbose(verbose: bool) {
+    VERBOSE.store(verbose, Ordering::Relaxed);
+}
+
+/// A command as a shell line, with the value following a password
+/// argument masked.
+pub fn masked_command(command: &Command) -> String {
+    let mut line = vec![command.get_program().to_string_lossy().into_owned()];
+    let mut secret = false;
+    for arg in command.get_args() {
+        let arg = arg.to_string_lossy();
+        if secret {
+            line.push("********".to_string());
+        } else if arg.is_empty() || arg.contains(char::is_whitespace) {
+            line.push(format!("'{}'", arg));
+        } else {
+            line.push(arg.to_string());
+        }
+        secret = arg == "password" || arg == "802-1x.password";
+    }
+
+    line.join(" ")
+}
+
+/// `Command::output` that honors `set_verbose`, passwords are masked in
+/// the trace.
+pub fn run(command: &mut Command) -> io::Result<Output> {
+    let verbose = VERBOSE.load(Ordering::Relaxed);
+    if verbose {
+        eprintln!("+ {}", masked_command(command));
+    }
+    let output = command.output()?;
+    if verbose {
+        eprint!("{}", String::from_utf8_lossy(&output.stdout));
+        eprint!("{}", String::from_utf8_lossy(&output.stderr));
+        eprintln!("+ {}", output.status);
+    }
+
+    Ok(output)
+}
+
+pub trait ScanBackend {
+    /// Short name used to select the backend, e.g. "nmcli"
+    fn name(&self) -> &'static str;
+
+    fn scan(&self) -> Result<Vec<Network>, Error>;
+}
+
+/// Scans through the `wifiscanner` crate, which runs `iw` on Linux.
+#[derive(Debug, Default)]
+pub struct WifiScannerBackend;
+
+impl ScanBackend for WifiScannerBackend {
+    fn name(&self) -> &'static str {
+        "wifiscanner"
+    }
+
+    fn scan(&self) -> Result<Vec<Network>, Error> {
+        if VERBOSE.load(Ordering::Relaxed) {
+            eprintln!("+ wifiscanner::scan(), its raw output is not available");
+        }
+        let networks = wifiscanner::scan().map_err(|e| Error::Scan(format!("{:?}", e)))?;
+
+        Ok(networks.into_iter().map(Network::from).collect())
+    }
+}
+
+/// Scans through NetworkManager's `nmcli dev wifi list`.
+#[derive(Debug, Default)]
+pub struct NmcliBackend {
+    /// Defaults to the first wireless interface, or every interface
+    /// NetworkManager manages when none is found
+    pub interface: Option<String>,
+}
+
+impl ScanBackend for NmcliBackend {
+    fn name(&self) -> &'static str {
+        "nmcli"
+    }
+
+    fn scan(&self) -> Result<Vec<Network>, Error> {
+        let mut nmcli = Command::new("nmcli");
+        nmcli.args([
+            "-t",
+            "-f",
+            "bssid,ssid,chan,freq,signal,security",
+            "dev",
+            "wifi",
+            "list",
+        ]);
+        let interface = self.interface.clone().or_else(default_wireless_interface);
+        if let Some(interface) = &interface {
+            nmcli.args(["ifname", interface]);
+        }
+        let nmcli = run(&mut nmcli).map_err(|e| Error::Scan(format!("cannot run nmcli: {}", e)))?;
+
+        if !nmcli.status.success() {
+            return Err(Error::Scan(
+                String::from_utf8_lossy(&nmcli.stderr).trim().to_string(),
+            ));
+        }
+
+        let mut networks = parse_nmcli_list(&String::from_utf8_lossy(&nmcli.stdout));
+        for network in &mut networks {
+            network.interface = interface.clone().unwrap_or_default();
+        }
+
+        Ok(networks)
+    }
+}
+
+fn parse_nmcli_list(output: &str) -> Vec<Network> {
+    output
+        .lines()
+        .filter_map(|line| match split_terse(line).as_slice() {
+            [bssid, ssid, channel, frequency, signal, security] => Some(Network {
+                mac: bssid.to_lowercase(),
+                ssid: decode_ssid(ssid),
+                channel: channel.parse::<u32>().unwrap_or_default(),
+                // e.g. "5180 MHz"
+                frequency: frequency
+                    .trim_end_matches("MHz")
+                    .trim()
+                    .parse::<u32>()
+                    .unwrap_or_default(),
+                interface: String::new(),
+                // nmcli does not list the information elements.
+                wps: None,
+                // nmcli only reports a 0-100 quality, map it back onto dBm.
+                signal_dbm: parse_signal(signal).map_or(f32::NAN, percent_to_dbm),
+                security: Security::parse(security),
+            }),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Scans by running `iw dev <interface> scan` directly, for systems
+/// without NetworkManager.
+#[derive(Debug, Default)]
+pub struct IwBackend {
+    /// Defaults to the first wireless interface
+    pub interface: Option<String>,
+}
+
+impl ScanBackend for IwBackend {
+    fn name(&self) -> &'static str {
+        "iw"
+    }
+
+    fn scan(&self) -> Result<Vec<Network>, Error> {
+        let iw =
+            find_command("iw").ok_or_else(|| Error::Scan("iw is not installed".to_string()))?;
+        let interface = match &self.interface {
+            Some(interface) => interface.clone(),
+            None => default_wireless_interface()
+                .ok_or_else(|| Error::Scan("no wireless interface found".to_string()))?,
+        };
+
+        let output = run(Command::new(iw).args(["dev", &interface, "scan"]))
+            .map_err(|e| Error::Scan(format!("cannot run iw: {}", e)))?;
+
+        if !output.status.success() {
+            return Err(Error::Scan(
+                String::from_utf8_lossy(&output.stderr).trim().to_string(),
+            ));
+        }
+
+        let mut networks = parse_iw_scan(&String::from_utf8_lossy(&output.stdout));
+        for network in &mut networks {
+            network.interface = interface.clone();
+        }
+
+        Ok(networks)
+    }
+}
+
+#[derive(Default)]
+struct IwBss {
+    network: Network,
+    frequency: Option<u32>,
+    privacy: bool,
+    wpa: bool,
+    wps: bool,
+    /// The `Authentication suites` of the RSN and WPA sections as iw prints
+    /// them, e.g. "PSK SAE" or "IEEE 802.1X/SHA-256"
+    rsn_suites: String,
+    wpa_suites: String,
+}
+
+impl IwBss {
+    fn finish(mut self) -> Network {
+        if let Some(frequency) = self.frequency {
+            self.network.frequency = frequency;
+        }
+        if let Some(channel) = self.frequency.and_then(frequency_to_channel) {
+            self.network.channel = channel;
+        }
+        self.network.security = self.security();
+        self.network.wps = Some(self.wps);
+
+        self.network
+    }
+
+    fn security(&self) -> Security {
+        // Suites such as "IEEE 802.1X" hold spaces, their names are looked
+        // for in the whole text rather than in its words.
+        if self.rsn_suites.contains("802.1X") || self.wpa_suites.contains("802.1X") {
+            Security::Enterprise
+        } else if self.rsn_suites.contains("SAE") {
+            Security::Wpa3Sae
+        } else if self.rsn_suites.contains("PSK") {
+            Security::Wpa2Psk
+        } else if self.wpa {
+            Security::WpaPsk
+        } else if self.privacy {
+            Security::Wep
+        } else {
+            Security::Open
+        }
+    }
+}
+
+#[derive(PartialEq)]
+enum IwSection {
+    None,
+    Rsn,
+    Wpa,
+}
+
+fn parse_iw_scan(output: &str) -> Vec<Network> {
+    let mut networks = Vec::new();
+    let mut bss: Option<IwBss> = None;
+    let mut section = IwSection::None;
+
+    for line in output.lines() {
+        if let Some(rest) = line.strip_prefix("BSS ") {
+            networks.extend(bss.take().map(IwBss::finish));
+            let mac = rest.split(|c: char| c == '(' || c.is_whitespace()).next();
+            bss = Some(IwBss {
+                network: Network {
+                    mac: mac.unwrap_or_default().to_lowercase(),
+                    signal_dbm: f32::NAN,
+                    ..Network::default()
+                },
+                ..IwBss::default()
+            });
+            section = IwSection::None;
+            continue;
+        }
+        let bss = match bss.as_mut() {
+            Some(bss) => bss,
+            None => continue,
+        };
+
+        // Attributes of the BSS are indented by one tab, the details of a
+        // section such as RSN by two.
+        if !line.starts_with("\t\t") {
+            section = IwSection::None;
+        }
+        let line = line.trim();
+
+        if let Some(value) = line.strip_prefix("SSID: ") {
+            bss.network.ssid = decode_ssid(value);
+        } else if let Some(value) = line.strip_prefix("signal: ") {
+            let value = value.trim_end_matches("dBm").trim();
+            bss.network.signal_dbm = parse_signal(value).unwrap_or(f32::NAN);
+        } else if let Some(value) = line.strip_prefix("freq: ") {
+            bss.frequency = value.parse::<f32>().ok().map(|freq| freq as u32);
+        } else if let Some(value) = line.strip_prefix("DS Parameter set: channel ") {
+            bss.network.channel = value.trim().parse::<u32>().unwrap_or_default();
+        } else if let Some(value) = line.strip_prefix("capability: ") {
+            bss.privacy = value.split_whitespace().any(|flag| flag == "Privacy");
+        } else if line.starts_with("RSN:") {
+            section = IwSection::Rsn;
+        } else if line.starts_with("WPA:") {
+            section = IwSection::Wpa;
+            bss.wpa = true;
+        } else if line.starts_with("WPS:") {
+            bss.wps = true;
+        }
+
+        let suites = line
+            .trim_start_matches("RSN:")
+            .trim_start_matches("WPA:")
+            .trim_start_matches(|c: char| c == '*' || c.is_whitespace())
+            .strip_prefix("Authentication suites: ");
+        if let Some(value) = suites {
+            match section {
+                IwSection::Rsn => bss.rsn_suites = value.to_string(),
+                IwSection::Wpa => bss.wpa_suites = value.to_string(),
+                IwSection::None => {}
+            }
+        }
+    }
+    networks.extend(bss.map(IwBss::finish));
+
+    networks
+}
+
+/// Resolve `program` against `$PATH` and the sbin directories, which are
+/// often missing from a regular user's `$PATH`.
+pub fn find_command(program: &str) -> Option<PathBuf> {
+    let path = env::var_os("PATH").unwrap_or_default();
+    env::split_paths(&path)
+        .chain(["/usr/sbin", "/sbin"].iter().map(PathBuf::from))
+        .map(|dir| dir.join(program))
+        .find(|candidate| candidate.is_file())
+}
+
+/// Whether `program` can be found in `$PATH` or the sbin directories.
+pub fn command_exists(program: &str) -> bool {
+    find_command(program).is_some()
+}
+
+/// The first backend whose program is installed: nmcli, then iw, then the
+/// wifiscanner crate for the platforms it supports beyond Linux.
+pub fn detect_backend() -> Box<dyn ScanBackend> {
+    detect_backend_on(None)
+}
+
+/// Like `detect_backend`, scanning through `interface`. The wifiscanner
+/// crate always picks the interface itself.
+pub fn detect_backend_on(interface: Option<String>) -> Box<dyn ScanBackend> {
+    if command_exists("nmcli") {
+        Box::new(NmcliBackend { interface })
+    } else if command_exists("iw") {
+        Box::new(IwBackend { interface })
+    } else {
+        Box::new(WifiScannerBackend)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ENTERPRISE: &str = "\
+BSS 00:11:22:33:44:55(on wlan0)
+\tfreq: 5180
+\tcapability: ESS Privacy SpectrumMgmt (0x0111)
+\tsignal: -58.00 dBm
+\tSSID: Corp
+\tRSN:\t * Version: 1
+\t\t * Group cipher: CCMP
+\t\t * Pairwise ciphers: CCMP
+\t\t * Authentication suites: IEEE 802.1X
+\t\t * Capabilities: 16-PTKSA-RC 1-GTKSA-RC (0x000c)
+BSS 66:77:88:99:aa:bb(on wlan0)
+\tfreq: 2437
+\tcapability: ESS Privacy (0x0411)
+\tsignal: -70.00 dBm
+\tSSID: Home
+\tRSN:\t * Version: 1
+\t\t * Authentication suites: PSK SAE
+BSS cc:dd:ee:ff:00:11(on wlan0)
+\tfreq: 2412
+\tcapability: ESS Privacy (0x0411)
+\tsignal: -80.00 dBm
+\tSSID: Old
+";
+
+    #[test]
+    fn iw_rsn_security() {
+        let networks = parse_iw_scan(ENTERPRISE);
+
+        assert_eq!(networks.len(), 3);
+        assert_eq!(networks[0].ssid, "Corp");
+        assert_eq!(networks[0].channel, 36);
+        assert_eq!(networks[0].signal_dbm, -58.0);
+        assert_eq!(networks[0].security, Security::Enterprise);
+        assert_eq!(networks[1].security, Security::Wpa3Sae);
+        assert_eq!(networks[2].security, Security::Wep);
+    }
+
+    #[test]
+    fn iw_multi_word_suites() {
+        for suites in ["FT/IEEE 802.1X", "IEEE 802.1X/SHA-256"] {
+            let output = format!(
+                "BSS 00:11:22:33:44:55(on wlan0)\n\tcapability: ESS Privacy\n\
+                 \tRSN:\t * Version: 1\n\t\t * Authentication suites: {}\n",
+                suites
+            );
+
+            assert_eq!(parse_iw_scan(&output)[0].security, Security::Enterprise);
+        }
+    }
+}
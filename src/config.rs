@@ -0,0 +1,124 @@
+/*-
+ * SPDX-License-Identifier: BSD-2-Clause
+ *
+ * BSD 2-Clause License
+ *
+ * Copyright (c) 2021, Marcelo Araujo <araujobsdport@gmail.com>
+ * All rights reserved.
+ *
+ * Redistribution and use in source and binary forms, with or without
+ * modification, are permitted provided that the following conditions are met:
+ *
+ * 1. Redistributions of source code must retain the above copyright notice, this
+ *    list of conditions and the following disclaimer.
+ *
+ * 2. Redistributions in binary form must reproduce the above copyright notice,
+ *    this list of conditions and the following disclaimer in the documentation
+ *    and/or other materials provided with the distribution.
+ *
+ * THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS"
+ * AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE
+ * IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE ARE
+ * DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT HOLDER OR CONTRIBUTORS BE LIABLE
+ * FOR ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL, EXEMPLARY, OR CONSEQUENTIAL
+ * DAMAGES (INCLUDING, BUT NOT LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR
+ * SERVICES; LOSS OF USE, DATA, OR PROFITS; OR BUSINESS INTERRUPTION) HOWEVER
+ * CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY,
+ * OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
+ * OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+ */
+
+use ifwifi::{SignalMeasure, Thresholds};
+use serde::Deserialize;
+use std::env;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+/// Settings read from `~/.config/ifwifi/config.toml`, e.g.
+///
+/// ```toml
+/// [thresholds]
+/// good = -62
+///
+/// [labels]
+/// good = "Bueno"
+/// ```
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    pub thresholds: Thresholds,
+    pub labels: Labels,
+}
+
+/// Replacements for the English signal quality labels, to localize them.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct Labels {
+    pub maximum: Option<String>,
+    pub excellent: Option<String>,
+    pub good: Option<String>,
+    pub reliable: Option<String>,
+    pub weak: Option<String>,
+    pub unreliable: Option<String>,
+    pub bad: Option<String>,
+}
+
+impl Labels {
+    /// The configured text of `measure`, or its English label.
+    pub fn label(&self, measure: SignalMeasure) -> &str {
+        let label = match measure {
+            SignalMeasure::Maximum => &self.maximum,
+            SignalMeasure::Excellent => &self.excellent,
+            SignalMeasure::Good => &self.good,
+            SignalMeasure::Reliable => &self.reliable,
+            SignalMeasure::Weak => &self.weak,
+            SignalMeasure::Unreliable => &self.unreliable,
+            SignalMeasure::Bad => &self.bad,
+        };
+
+        label.as_deref().unwrap_or_else(|| measure.label())
+    }
+
+    /// Characters in the longest label, the signal column pads to it.
+    pub fn width(&self) -> usize {
+        [
+            SignalMeasure::Maximum,
+            SignalMeasure::Excellent,
+            SignalMeasure::Good,
+            SignalMeasure::Reliable,
+            SignalMeasure::Weak,
+            SignalMeasure::Unreliable,
+            SignalMeasure::Bad,
+        ]
+        .iter()
+        .map(|&measure| self.label(measure).chars().count())
+        .max()
+        .unwrap_or_default()
+    }
+}
+
+impl Config {
+    /// Read the config file, a missing one leaves every default in place.
+    pub fn load() -> Result<Config, String> {
+        let path = match config_path() {
+            Some(path) => path,
+            None => return Ok(Config::default()),
+        };
+
+        match fs::read_to_string(&path) {
+            Ok(contents) => toml::from_str(&contents)
+                .map_err(|e| format!("invalid config {}: {}", path.display(), e)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Config::default()),
+            Err(e) => Err(format!("cannot read config {}: {}", path.display(), e)),
+        }
+    }
+}
+
+fn config_path() -> Option<PathBuf> {
+    let config_home = env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+
+    Some(config_home.join("ifwifi").join("config.toml"))
+}
@@ -0,0 +1,626 @@
+/*-
+ * SPDX-License-Identifier: BSD-2-Clause
+ *
+ * BSD 2-Clause License
+ *
+ * Copyright (c) 2021, Marcelo Araujo <araujobsdport@gmail.com>
+ * All rights reserved.
+ *
+ * Redistribution and use in source and binary forms, with or without
+ * modification, are permitted provided that the following conditions are met:
+ *
+ * 1. Redistributions of source code must retain the above copyright notice, this
+ *    list of conditions and the following disclaimer.
+ *
+ * 2. Redistributions in binary form must reproduce the above copyright notice,
+ *    this list of conditions and the following disclaimer in the documentation
+ *    and/or other materials provided with the distribution.
+ *
+ * THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS"
+ * AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE
+ * IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE ARE
+ * DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT HOLDER OR CONTRIBUTORS BE LIABLE
+ * FOR ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL, EXEMPLARY, OR CONSEQUENTIAL
+ * DAMAGES (INCLUDING, BUT NOT LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR
+ * SERVICES; LOSS OF USE, DATA, OR PROFITS; OR BUSINESS INTERRUPTION) HOWEVER
+ * CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY,
+ * OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
+ * OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+ */
+
+//! Scanning and signal classification behind the `ifwifi` command line
+//! tool, usable on its own without the CLI argument parsing.
+
+pub mod backend;
+pub mod oui;
+pub mod regulatory;
+
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+const SYSFS_NET: &str = "/sys/class/net";
+
+#[derive(Debug)]
+pub enum Error {
+    /// The scanning backend failed or is not installed
+    Scan(String),
+    /// The network interfaces under `/sys/class/net` could not be read
+    Sysfs(io::Error),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Scan(e) => write!(f, "scan failed: {}; is your wireless interface up?", e),
+            Error::Sysfs(e) => write!(
+                f,
+                "cannot read {}: {}; is this a Linux system?",
+                SYSFS_NET, e
+            ),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<Error> for String {
+    fn from(error: Error) -> Self {
+        error.to_string()
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum SignalMeasure {
+    Maximum,
+    Excellent,
+    Good,
+    Reliable,
+    Weak,
+    Unreliable,
+    Bad,
+}
+
+impl SignalMeasure {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SignalMeasure::Maximum => "maximum",
+            SignalMeasure::Excellent => "excellent",
+            SignalMeasure::Good => "good",
+            SignalMeasure::Reliable => "reliable",
+            SignalMeasure::Weak => "weak",
+            SignalMeasure::Unreliable => "unreliable",
+            SignalMeasure::Bad => "bad",
+        }
+    }
+
+    /// Capitalized for display, e.g. "Good"
+    pub fn label(&self) -> &'static str {
+        match self {
+            SignalMeasure::Maximum => "Maximum",
+            SignalMeasure::Excellent => "Excellent",
+            SignalMeasure::Good => "Good",
+            SignalMeasure::Reliable => "Reliable",
+            SignalMeasure::Weak => "Weak",
+            SignalMeasure::Unreliable => "Unreliable",
+            SignalMeasure::Bad => "Bad",
+        }
+    }
+}
+
+/// A wireless network seen by a scan.
+#[derive(Clone, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Network {
+    pub mac: String,
+    pub ssid: String,
+    /// 0 when the backend did not report a usable channel
+    pub channel: u32,
+    /// Center frequency in MHz, 0 when the backend did not report it
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub frequency: u32,
+    /// NaN when the backend did not report a usable signal
+    #[cfg_attr(feature = "serde", serde(deserialize_with = "deserialize_signal"))]
+    pub signal_dbm: f32,
+    pub security: Security,
+    /// The interface the network was heard through, empty when the scan
+    /// did not say
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub interface: String,
+    /// Whether the access point advertises WPS, `None` when the backend
+    /// does not say
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub wps: Option<bool>,
+}
+
+/// Two unparseable signals are both NaN, they compare equal so a scan read
+/// back from JSON equals the one written.
+impl PartialEq for Network {
+    fn eq(&self, other: &Self) -> bool {
+        let same_signal = self.signal_dbm == other.signal_dbm
+            || (self.signal_dbm.is_nan() && other.signal_dbm.is_nan());
+
+        self.mac == other.mac
+            && self.ssid == other.ssid
+            && self.channel == other.channel
+            && self.frequency == other.frequency
+            && same_signal
+            && self.security == other.security
+            && self.interface == other.interface
+            && self.wps == other.wps
+    }
+}
+
+impl Network {
+    pub fn quality(&self, thresholds: &Thresholds) -> SignalMeasure {
+        dBm_signal_measure(self.signal_dbm, thresholds)
+    }
+
+    /// The frequency tells the 6GHz channels apart from the 2.4GHz and 5GHz
+    /// ones reusing their numbers, the channel is only a fallback.
+    pub fn band(&self) -> &'static str {
+        match frequency_to_band(self.frequency) {
+            "?" => channel_to_band(self.channel),
+            band => band,
+        }
+    }
+
+    /// The reported frequency, or the one of the channel when the backend
+    /// did not report it.
+    pub fn center_frequency(&self) -> Option<u32> {
+        match self.frequency {
+            0 => channel_to_frequency(self.band(), self.channel),
+            frequency => Some(frequency),
+        }
+    }
+
+    /// A hidden network broadcasts an empty SSID.
+    pub fn is_hidden(&self) -> bool {
+        self.ssid.is_empty()
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.security == Security::Open
+    }
+
+    /// The manufacturer of the access point, from the bundled OUI table.
+    pub fn vendor(&self) -> Option<&'static str> {
+        oui::vendor(&self.mac)
+    }
+}
+
+/// serde_json writes a NaN signal as `null`, read it back as NaN.
+#[cfg(feature = "serde")]
+fn deserialize_signal<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<f32, D::Error> {
+    let signal: Option<f32> = serde::Deserialize::deserialize(deserializer)?;
+
+    Ok(signal.unwrap_or(f32::NAN))
+}
+
+impl From<wifiscanner::Wifi> for Network {
+    fn from(wifi: wifiscanner::Wifi) -> Self {
+        Network {
+            signal_dbm: normalize_signal(&wifi.signal_level),
+            channel: wifi.channel.trim().parse::<u32>().unwrap_or_default(),
+            frequency: 0,
+            interface: String::new(),
+            wps: None,
+            ssid: decode_ssid(&wifi.ssid),
+            mac: wifi.mac,
+            // wifiscanner leaves the security empty on Linux, which does not
+            // mean the network is open.
+            security: if cfg!(target_os = "linux") && wifi.security.is_empty() {
+                Security::Unknown(String::new())
+            } else {
+                Security::parse(&wifi.security)
+            },
+        }
+    }
+}
+
+/// Scan the networks in range with the first available backend.
+pub fn scan_networks() -> Result<Vec<Network>, Error> {
+    backend::detect_backend().scan()
+}
+
+/// `iw` prints every non-ASCII byte of an SSID as a `\xNN` escape, turn
+/// them back into bytes so UTF-8 names like `Bohórquez` render correctly.
+pub(crate) fn decode_ssid(ssid: &str) -> String {
+    let raw = ssid.as_bytes();
+    let mut bytes = Vec::with_capacity(raw.len());
+    let mut i = 0;
+
+    while i < raw.len() {
+        if raw[i] == b'\\' && raw.get(i + 1) == Some(&b'x') {
+            let byte = ssid
+                .get(i + 2..i + 4)
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+            if let Some(byte) = byte {
+                bytes.push(byte);
+                i += 4;
+                continue;
+            }
+        }
+        bytes.push(raw[i]);
+        i += 1;
+    }
+
+    String::from_utf8_lossy(&bytes).into_owned()
+}
+
+/// Split a line of nmcli's terse (`-t`) output into its fields, nmcli
+/// escapes a literal `:` as `\:` and a backslash as `\\`.
+pub fn split_terse(line: &str) -> Vec<String> {
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut chars = line.trim_end_matches(['\r', '\n']).chars();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => field.extend(chars.next()),
+            ':' => fields.push(std::mem::take(&mut field)),
+            _ => field.push(c),
+        }
+    }
+    fields.push(field);
+
+    fields
+}
+
+/// Channel numbers are reused across bands, without the frequency only the
+/// 6GHz channels above the 5GHz range can be told apart.
+pub fn channel_to_band(channel: u32) -> &'static str {
+    match channel {
+        1..=14 => "2.4GHz",
+        32..=177 => "5GHz",
+        181..=233 => "6GHz",
+        _ => "?",
+    }
+}
+
+pub fn frequency_to_band(frequency: u32) -> &'static str {
+    match frequency {
+        2400..=2500 => "2.4GHz",
+        5150..=5895 => "5GHz",
+        5925..=7125 => "6GHz",
+        _ => "?",
+    }
+}
+
+/// The center frequency in MHz of a channel of `band`.
+pub fn channel_to_frequency(band: &str, channel: u32) -> Option<u32> {
+    match (band, channel) {
+        ("2.4GHz", 14) => Some(2484),
+        ("2.4GHz", 1..=13) => Some(2407 + channel * 5),
+        ("5GHz", 32..=177) => Some(5000 + channel * 5),
+        ("6GHz", 1..=233) => Some(5950 + channel * 5),
+        _ => None,
+    }
+}
+
+/// The channel of a center frequency in MHz.
+pub fn frequency_to_channel(frequency: u32) -> Option<u32> {
+    match frequency {
+        2484 => Some(14),
+        2412..=2472 => Some((frequency - 2407) / 5),
+        5160..=5885 => Some((frequency - 5000) / 5),
+        5955..=7115 => Some((frequency - 5950) / 5),
+        _ => None,
+    }
+}
+
+/// Backends spell an unencrypted network as an empty string, "--", "Open"
+/// or "None".
+pub fn is_open_security(security: &str) -> bool {
+    Security::parse(security) == Security::Open
+}
+
+/// The strongest protection a network advertises.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum Security {
+    #[default]
+    Open,
+    Wep,
+    WpaPsk,
+    Wpa2Psk,
+    Wpa3Sae,
+    /// 802.1X (EAP) authentication, whatever the WPA version
+    Enterprise,
+    /// The text the backend reported, empty when it reported nothing
+    Unknown(String),
+}
+
+impl Security {
+    /// Parse the security as spelled by nmcli, iw or wifiscanner, e.g.
+    /// "WPA1 WPA2", "WPA2 802.1X" or "--". Mixed modes resolve to the
+    /// strongest one.
+    pub fn parse(security: &str) -> Security {
+        let security = security.trim();
+        let upper = security.to_uppercase();
+        if upper.is_empty() || upper == "--" || upper == "OPEN" || upper == "NONE" {
+            Security::Open
+        } else if upper.contains("802.1X") || upper.contains("EAP") {
+            Security::Enterprise
+        } else if upper.contains("WPA3") || upper.contains("SAE") {
+            Security::Wpa3Sae
+        } else if upper.contains("WPA2") || upper.contains("RSN") {
+            Security::Wpa2Psk
+        } else if upper.contains("WPA") {
+            Security::WpaPsk
+        } else if upper.contains("WEP") {
+            Security::Wep
+        } else {
+            Security::Unknown(security.to_string())
+        }
+    }
+
+    pub fn risk(&self) -> Risk {
+        match self {
+            Security::Open => Risk::High,
+            Security::Wep | Security::WpaPsk => Risk::Medium,
+            Security::Wpa2Psk | Security::Wpa3Sae | Security::Enterprise => Risk::Low,
+            Security::Unknown(_) => Risk::Unknown,
+        }
+    }
+}
+
+impl fmt::Display for Security {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Security::Open => write!(f, "Open"),
+            Security::Wep => write!(f, "WEP"),
+            Security::WpaPsk => write!(f, "WPA"),
+            Security::Wpa2Psk => write!(f, "WPA2"),
+            Security::Wpa3Sae => write!(f, "WPA3"),
+            Security::Enterprise => write!(f, "802.1X"),
+            Security::Unknown(security) if security.is_empty() => write!(f, "?"),
+            Security::Unknown(security) => write!(f, "{}", security),
+        }
+    }
+}
+
+/// How exposed the traffic of a network is given its security.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum Risk {
+    /// WPA2, WPA3 or 802.1X
+    Low,
+    /// WEP or WPA1, both broken
+    Medium,
+    /// No encryption at all
+    High,
+    Unknown,
+}
+
+pub fn security_risk(security: &str) -> Risk {
+    Security::parse(security).risk()
+}
+
+pub fn parse_signal(signal_level: &str) -> Option<f32> {
+    signal_level.trim().parse::<f32>().ok()
+}
+
+/// Read a signal into dBm whatever the driver reported: negative dBm such
+/// as "-67" or "-67 dBm", a 0-100 quality such as "70" or a link quality
+/// ratio such as "70/70". A bare "0" is a 0% quality, not 0 dBm. NaN when
+/// it is none of them.
+pub fn normalize_signal(raw: &str) -> f32 {
+    let raw = raw.trim().trim_end_matches("dBm").trim();
+
+    if let Some((quality, max)) = raw.split_once('/') {
+        return match (parse_signal(quality), parse_signal(max)) {
+            (Some(quality), Some(max)) if max > 0.0 => percent_to_dbm(quality / max * 100.0),
+            _ => f32::NAN,
+        };
+    }
+
+    match parse_signal(raw) {
+        Some(dbm) if dbm < 0.0 => dbm,
+        Some(percent) if percent <= 100.0 => percent_to_dbm(percent),
+        _ => f32::NAN,
+    }
+}
+
+/// The reverse of `dbm_to_percent`.
+pub fn percent_to_dbm(percent: f32) -> f32 {
+    percent / 2.0 - 100.0
+}
+
+/// The weakest signal, in dBm, still classified as each `SignalMeasure`.
+/// `Bad` has no cutoff, it is anything weaker than `unreliable`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct Thresholds {
+    pub maximum: f32,
+    pub excellent: f32,
+    pub good: f32,
+    pub reliable: f32,
+    pub weak: f32,
+    pub unreliable: f32,
+}
+
+impl Default for Thresholds {
+    fn default() -> Self {
+        Thresholds {
+            maximum: -30.00,
+            excellent: -50.00,
+            good: -60.00,
+            reliable: -67.00,
+            weak: -70.00,
+            unreliable: -80.00,
+        }
+    }
+}
+
+#[allow(non_snake_case)]
+pub fn dBm_signal_measure(signal: f32, thresholds: &Thresholds) -> SignalMeasure {
+    if signal >= thresholds.maximum {
+        SignalMeasure::Maximum
+    } else if signal >= thresholds.excellent {
+        SignalMeasure::Excellent
+    } else if signal >= thresholds.good {
+        SignalMeasure::Good
+    } else if signal >= thresholds.reliable {
+        SignalMeasure::Reliable
+    } else if signal >= thresholds.weak {
+        SignalMeasure::Weak
+    } else if signal >= thresholds.unreliable {
+        SignalMeasure::Unreliable
+    } else {
+        SignalMeasure::Bad
+    }
+}
+
+/// Map a signal onto a 0-100% quality, -100 dBm or weaker is 0% and
+/// -50 dBm or stronger is 100%.
+pub fn dbm_to_percent(dbm: f32) -> u8 {
+    if dbm.is_nan() {
+        return 0;
+    }
+
+    (2.0 * (dbm + 100.0)).clamp(0.0, 100.0) as u8
+}
+
+/// Names of the interfaces exposing a `wireless` directory in sysfs.
+pub fn wireless_interfaces() -> Result<Vec<String>, Error> {
+    let entries = fs::read_dir(SYSFS_NET).map_err(Error::Sysfs)?;
+
+    let mut interfaces: Vec<String> = entries
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| is_wireless(&entry.file_name().to_string_lossy()))
+        .map(|entry| entry.file_name().to_string_lossy().into_owned())
+        .collect();
+    interfaces.sort();
+
+    Ok(interfaces)
+}
+
+/// Whether `interface` exposes a `wireless` directory in sysfs.
+pub fn is_wireless(interface: &str) -> bool {
+    Path::new(SYSFS_NET)
+        .join(interface)
+        .join("wireless")
+        .is_dir()
+}
+
+pub fn default_wireless_interface() -> Option<String> {
+    wireless_interfaces().ok()?.into_iter().next()
+}
+
+/// The `operstate` of an interface as reported by sysfs, e.g. "up".
+pub fn operstate(interface: &str) -> String {
+    fs::read_to_string(Path::new(SYSFS_NET).join(interface).join("operstate"))
+        .map(|state| state.trim().to_string())
+        .unwrap_or_else(|_| "unknown".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decode_ssid_utf8() {
+        let ssid = decode_ssid("ClemenciaBoh\\xc3\\xb3rquez");
+
+        assert_eq!(ssid, "ClemenciaBohórquez");
+        assert!(!ssid.contains("\\xc3\\xb3"));
+    }
+
+    #[test]
+    fn decode_ssid_malformed_escape() {
+        assert_eq!(decode_ssid("Cafe\\xZ"), "Cafe\\xZ");
+        assert_eq!(decode_ssid("Cafe\\xZ1bar"), "Cafe\\xZ1bar");
+    }
+
+    #[test]
+    fn normalize_signal_formats() {
+        assert_eq!(normalize_signal("-67"), -67.0);
+        assert_eq!(normalize_signal("-67 dBm"), -67.0);
+        assert_eq!(normalize_signal("70"), -65.0);
+        assert_eq!(normalize_signal("70/70"), -50.0);
+        assert!(normalize_signal("garbage").is_nan());
+    }
+
+    #[test]
+    fn normalize_signal_zero_quality() {
+        let signal = normalize_signal("0");
+
+        assert_eq!(signal, -100.0);
+        assert_eq!(
+            dBm_signal_measure(signal, &Thresholds::default()),
+            SignalMeasure::Bad
+        );
+    }
+
+    #[test]
+    fn dbm_to_percent_anchors() {
+        assert_eq!(dbm_to_percent(-50.0), 100);
+        assert_eq!(dbm_to_percent(-100.0), 0);
+        assert_eq!(dbm_to_percent(-75.0), 50);
+        assert_eq!(dbm_to_percent(-20.0), 100);
+        assert_eq!(dbm_to_percent(-110.0), 0);
+    }
+
+    #[test]
+    fn dbm_to_percent_nan() {
+        assert_eq!(dbm_to_percent(f32::NAN), 0);
+    }
+
+    #[test]
+    fn band_of_6ghz_channel() {
+        let network = Network {
+            channel: 5,
+            frequency: 5975,
+            ..Network::default()
+        };
+
+        assert_eq!(network.band(), "6GHz");
+        assert_eq!(frequency_to_band(5975), "6GHz");
+    }
+
+    #[test]
+    fn band_without_frequency() {
+        let network = Network {
+            channel: 36,
+            ..Network::default()
+        };
+
+        assert_eq!(network.band(), "5GHz");
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn networks_json_round_trip() {
+        let networks = vec![
+            Network {
+                mac: "aa:bb:cc:dd:ee:ff".to_string(),
+                ssid: "Home".to_string(),
+                channel: 36,
+                frequency: 5180,
+                signal_dbm: -55.0,
+                security: Security::Wpa2Psk,
+                interface: "wlan0".to_string(),
+                wps: Some(true),
+            },
+            Network {
+                mac: "11:22:33:44:55:66".to_string(),
+                channel: 6,
+                signal_dbm: f32::NAN,
+                security: Security::Unknown(String::new()),
+                ..Network::default()
+            },
+        ];
+
+        let json = serde_json::to_string(&networks).unwrap();
+        let read: Vec<Network> = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(read, networks);
+    }
+}
@@ -28,39 +28,185 @@
  * OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
  */
 
-use clap::{CommandFactory, Parser, Subcommand};
+mod config;
+#[cfg(feature = "keyring")]
+mod secrets;
+mod state;
+
+use chrono::Local;
+use clap::{Args, CommandFactory, Parser, Subcommand, ValueEnum};
 use colored::*;
-use prettytable::{format, row, Row, Table};
+use ifwifi::backend::{
+    command_exists, detect_backend, detect_backend_on, find_command, masked_command, run,
+    set_verbose, IwBackend, NmcliBackend, ScanBackend, WifiScannerBackend,
+};
+use ifwifi::regulatory::{regulatory_domain, RegulatoryDomain};
+use ifwifi::{
+    dBm_signal_measure, dbm_to_percent, default_wireless_interface, is_wireless, operstate,
+    parse_signal, percent_to_dbm, split_terse, wireless_interfaces, Network, Risk, Security,
+    SignalMeasure,
+};
+use prettytable::{format, row, Cell, Row, Table};
+use qrcode::render::unicode;
+use qrcode::QrCode;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use state::{LastConnection, SignalHistory};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::env;
+use std::fmt;
+use std::fs;
+use std::io::{self, IsTerminal, Write};
+use std::path::{Path, PathBuf};
 use std::process::exit;
 use std::process::Command;
-use wifi_rs::prelude::*;
-use wifi_rs::WiFi;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant};
 
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None, arg_required_else_help(true))]
 struct Cli {
+    /// When to color the output, `auto` also honors the NO_COLOR environment variable
+    #[arg(
+        long,
+        value_name = "WHEN",
+        value_enum,
+        global = true,
+        default_value_t = ColorWhen::Auto
+    )]
+    color: ColorWhen,
+
+    /// Disable colored output, same as `--color never`
+    #[arg(long, global = true)]
+    no_color: bool,
+
+    /// Keep colors but never blink
+    #[arg(long, global = true)]
+    no_blink: bool,
+
+    /// Only print errors and the output asked for, not the progress and
+    /// confirmation lines
+    #[arg(short, long, global = true)]
+    quiet: bool,
+
+    /// Print the commands run and their raw output to stderr
+    #[arg(short, long, global = true)]
+    verbose: bool,
+
     #[command(subcommand)]
     command: Option<Commands>,
 }
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum ColorWhen {
+    /// Only when printing to a terminal
+    Auto,
+    Always,
+    Never,
+}
+
+impl ColorWhen {
+    fn enabled(self) -> bool {
+        match self {
+            ColorWhen::Auto => io::stdout().is_terminal() && env::var_os("NO_COLOR").is_none(),
+            ColorWhen::Always => true,
+            ColorWhen::Never => false,
+        }
+    }
+}
+
 #[derive(Subcommand, Debug)]
 enum Commands {
     /// Connect to an Access Point
-    Connect {
+    Connect(ConnectArgs),
+    /// Connect again to the last network joined with `connect`
+    Reconnect {},
+    /// Disconnect from the current Access Point
+    Disconnect {
+        /// Wireless interface to disconnect [default: first wireless interface]
+        #[arg(short, long)]
+        interface: Option<String>,
+    },
+    /// List the networks NetworkManager has a saved profile for
+    Known {
+        /// Scan to mark the saved networks currently in range
+        #[arg(long)]
+        in_range: bool,
+    },
+    /// Delete the saved NetworkManager profile of a network
+    Forget {
         /// SSID of wireless network
         #[arg(short, long)]
         ssid: String,
+    },
+    /// Scan wireless network
+    Scan(ScanArgs),
 
-        /// Password of the wireless network
-        #[arg(short, long)]
-        password: String,
+    /// Show the active connection
+    Status {
+        /// Print the connection as JSON, every field but `connected` is
+        /// null when disconnected
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// List wireless interfaces
+    Interfaces {},
+
+    /// Count the networks on every channel to find the least crowded one
+    Analyze {
+        /// Print the count and strongest signal of every channel as JSON
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// Check which tools and interfaces are available
+    Doctor {},
+
+    /// Turn the wireless radio on or off through rfkill
+    Radio {
+        #[arg(value_enum)]
+        state: RadioState,
+    },
+
+    /// Compare two scans saved with `scan --save`
+    Diff {
+        /// The earlier scan
+        old: PathBuf,
+
+        /// The later scan
+        new: PathBuf,
+
+        /// Only report a signal that changed by at least this many dB
+        #[arg(long, value_name = "DB", default_value_t = 5.0)]
+        threshold: f32,
+    },
+
+    /// Bring a wireless interface up or down through `ip link`
+    Link {
+        #[arg(value_enum)]
+        action: LinkAction,
 
-        /// Wireless interface to connect through
-        #[arg(short, long, default_value = "wlan0")]
+        /// Wireless interface to bring up or down
         interface: String,
     },
-    /// Scan wireless network
-    Scan {},
+
+    /// Print a QR code guests can scan to join a network
+    Qr {
+        /// SSID of wireless network
+        #[arg(short, long)]
+        ssid: String,
+
+        /// Password of the wireless network, not needed with `--security nopass`
+        #[arg(short, long)]
+        password: Option<String>,
+
+        /// Security of the wireless network
+        #[arg(long, value_enum, default_value_t = QrSecurity::Wpa)]
+        security: QrSecurity,
+    },
 
     /// Generate shell completions
     Completions {
@@ -70,168 +216,2772 @@ enum Commands {
     },
 }
 
+#[derive(Args, Debug)]
+struct ConnectArgs {
+    /// SSID of wireless network
+    #[arg(short, long)]
+    ssid: String,
+
+    /// Password of the wireless network, `-` reads it from stdin. Prompted
+    /// for when omitted
+    #[arg(short, long)]
+    password: Option<String>,
+
+    /// Read the password from the first line of this file
+    #[arg(long, value_name = "PATH", conflicts_with = "password")]
+    password_file: Option<PathBuf>,
+
+    /// The network is open and needs no password
+    #[arg(long, conflicts_with_all = ["password", "password_file"])]
+    open: bool,
+
+    /// Join an open network without asking for confirmation
+    #[arg(short, long)]
+    yes: bool,
+
+    /// Use the password a previous connect saved in the keyring
+    #[cfg(feature = "keyring")]
+    #[arg(long, conflicts_with_all = ["password", "password_file", "open"])]
+    from_keyring: bool,
+
+    /// Wireless interface to connect through [default: first wireless interface]
+    #[arg(short, long)]
+    interface: Option<String>,
+
+    /// Try to associate up to this many times before giving up
+    #[arg(long, value_name = "N", default_value_t = 1, value_parser = clap::value_parser!(u32).range(1..))]
+    retry: u32,
+
+    /// Seconds to wait between two attempts
+    #[arg(long, value_name = "SECONDS", default_value_t = 2)]
+    retry_delay: u64,
+
+    /// Wait until the connection is active and has an IP address
+    #[arg(long)]
+    verify: bool,
+
+    /// Seconds `--verify` waits for an IP address
+    #[arg(
+        long,
+        value_name = "SECONDS",
+        default_value_t = 20,
+        requires = "verify"
+    )]
+    verify_timeout: u64,
+
+    /// Shell command to run once connected, with IFWIFI_SSID and
+    /// IFWIFI_IFACE set
+    #[arg(long, value_name = "COMMAND")]
+    exec: Option<String>,
+
+    /// When the SSID is broadcast on several bands, join its strongest
+    /// access point on this one
+    #[arg(long, value_enum, value_name = "BAND")]
+    prefer_band: Option<Band>,
+
+    /// Join the access point with this MAC, it must broadcast the SSID
+    #[arg(long, value_name = "MAC", conflicts_with = "prefer_band")]
+    bssid: Option<String>,
+
+    /// Join a WPA-Enterprise (802.1X) network, the password is the one of
+    /// the identity
+    #[arg(long, requires = "identity", conflicts_with_all = ["open", "bssid", "prefer_band"])]
+    enterprise: bool,
+
+    /// User name to authenticate as with `--enterprise`
+    #[arg(long, requires = "enterprise")]
+    identity: Option<String>,
+
+    /// EAP method of `--enterprise`, both tunnel MSCHAPv2
+    #[arg(long, value_enum, default_value_t = EapMethod::Peap, requires = "enterprise")]
+    eap_method: EapMethod,
+
+    /// Identity sent outside of the tunnel with `--enterprise`
+    #[arg(long, requires = "enterprise")]
+    anonymous_identity: Option<String>,
+
+    /// CA certificate the server of `--enterprise` is checked against
+    #[arg(long, value_name = "PATH", requires = "enterprise")]
+    ca_cert: Option<PathBuf>,
+
+    /// Save the connection under this name rather than the SSID, to keep
+    /// several profiles for one network
+    #[arg(long, value_name = "NAME")]
+    profile_name: Option<String>,
+
+    /// Print the network, the interface and the nmcli commands a connect
+    /// would run, then exit without connecting
+    #[arg(long)]
+    dry_run: bool,
+}
+
+impl ConnectArgs {
+    fn enterprise(&self) -> Option<Enterprise<'_>> {
+        Some(Enterprise {
+            identity: self.identity.as_deref().filter(|_| self.enterprise)?,
+            method: self.eap_method,
+            anonymous_identity: self.anonymous_identity.as_deref(),
+            ca_cert: self.ca_cert.as_deref(),
+        })
+    }
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum EapMethod {
+    Peap,
+    Ttls,
+}
+
+impl EapMethod {
+    /// The method as named by nmcli's `802-1x.eap`
+    fn name(self) -> &'static str {
+        match self {
+            EapMethod::Peap => "peap",
+            EapMethod::Ttls => "ttls",
+        }
+    }
+}
+
+/// How `connect` joins a network, beyond its SSID and password.
+#[derive(Debug, Default)]
+struct JoinOptions<'a> {
+    /// Access point to pin the connection to
+    bssid: Option<&'a str>,
+    /// Name of the saved profile, NetworkManager names it after the SSID
+    profile: Option<&'a str>,
+    enterprise: Option<Enterprise<'a>>,
+}
+
+/// The 802.1X settings of `connect --enterprise`.
 #[derive(Debug)]
-enum SignalMeasure {
-    Maximum,
-    Excellent,
-    Good,
-    Reliable,
-    Weak,
-    Unreliable,
-    Bad,
-}
-
-fn scan_table_format(network_info: &wifiscanner::Wifi) -> Row {
-    let signal_level =
-        match dBm_signal_measure(network_info.signal_level.parse::<f32>().unwrap_or_default()) {
-            SignalMeasure::Maximum => {
-                format!(
-                    "{} ({})",
-                    "Maximum".green().bold().blink(),
-                    network_info.signal_level
-                )
-            }
-            SignalMeasure::Excellent => {
-                format!(
-                    "{} ({})",
-                    "Excellent".green().bold().blink(),
-                    network_info.signal_level
-                )
-            }
-            SignalMeasure::Good => {
-                format!("{} ({})", "Good".green().blink(), network_info.signal_level)
-            }
-            SignalMeasure::Reliable => {
-                format!(
-                    "{} ({})",
-                    "Reliable".yellow().bold().blink(),
-                    network_info.signal_level
-                )
-            }
-            SignalMeasure::Weak => {
-                format!("{} ({})", "Weak".yellow(), network_info.signal_level)
-            }
-            SignalMeasure::Unreliable => {
-                format!("{} ({})", "Unreliable".red(), network_info.signal_level)
-            }
-            SignalMeasure::Bad => {
-                format!("{} ({})", "Bad".red().bold(), network_info.signal_level)
-            }
-        };
+struct Enterprise<'a> {
+    identity: &'a str,
+    method: EapMethod,
+    anonymous_identity: Option<&'a str>,
+    ca_cert: Option<&'a Path>,
+}
 
-    if is_connected(&network_info.ssid) {
-        row![
-            format!("{} {}", "*".green().bold().blink(), network_info.mac),
-            network_info.ssid.yellow().bold(),
-            network_info.channel.white().bold(),
-            signal_level,
-            network_info.security
-        ]
-    } else {
-        row![
-            network_info.mac,
-            network_info.ssid.yellow().bold(),
-            network_info.channel.white().bold(),
-            signal_level,
-            network_info.security
+#[derive(Args, Debug)]
+struct ScanArgs {
+    /// How to print the networks
+    #[arg(long, value_enum, default_value_t = Format::Table)]
+    format: Format,
+
+    /// Same as `--format json`
+    #[arg(long, conflicts_with = "format")]
+    json: bool,
+
+    /// Same as `--format csv`
+    #[arg(long, conflicts_with_all = ["format", "json"])]
+    csv: bool,
+
+    /// Program used to scan
+    #[arg(long, value_enum, default_value_t = BackendKind::Auto)]
+    backend: BackendKind,
+
+    /// Scan again, up to 3 times, when no network at all was found
+    #[arg(long, conflicts_with = "load")]
+    rescan: bool,
+
+    /// Give up on a scan the backend has not finished after this many
+    /// seconds
+    #[arg(long, value_name = "SECONDS", conflicts_with = "load", value_parser = clap::value_parser!(u64).range(1..))]
+    timeout: Option<u64>,
+
+    /// Scan this many times and show the average signal of every BSSID
+    #[arg(long, value_name = "N", default_value_t = 1, conflicts_with = "load", value_parser = clap::value_parser!(u32).range(1..))]
+    samples: u32,
+
+    /// Print how long the backend took to stderr
+    #[arg(long, conflicts_with = "load")]
+    timing: bool,
+
+    /// Wireless interface to scan with [default: first wireless interface]
+    #[arg(short, long)]
+    interface: Option<String>,
+
+    /// Scan with every wireless interface and merge the results, adding a
+    /// column with the interface hearing each network best
+    #[arg(long, conflicts_with = "interface")]
+    all_interfaces: bool,
+
+    /// Also write the networks found to this file, as JSON
+    #[arg(long, value_name = "PATH")]
+    save: Option<PathBuf>,
+
+    /// Show the networks of a file written by `--save` instead of scanning
+    #[arg(long, value_name = "PATH", conflicts_with_all = ["backend", "interface"])]
+    load: Option<PathBuf>,
+
+    /// Sort the networks by the given key
+    #[arg(long, value_enum, default_value_t = SortKey::Signal)]
+    sort: SortKey,
+
+    /// How the table is drawn, `markdown` pastes into an issue
+    #[arg(long, value_enum, default_value_t = TableStyle::Clean)]
+    table_style: TableStyle,
+
+    /// Print a separate table for every band
+    #[arg(long)]
+    group_by_band: bool,
+
+    /// Invert the sort order. `--top` and `--limit` then keep the weakest
+    /// or last networks
+    #[arg(long)]
+    reverse: bool,
+
+    /// Show the connected network first, whatever the sort
+    #[arg(long)]
+    connected_first: bool,
+
+    /// Only show networks whose SSID contains this text (case-insensitive)
+    #[arg(long)]
+    filter: Option<String>,
+
+    /// Only show networks without encryption
+    #[arg(long)]
+    open_only: bool,
+
+    /// Only show the networks that can be joined right now: open or with a
+    /// saved profile, and a signal of at least the `reliable` threshold
+    #[arg(long)]
+    usable: bool,
+
+    /// Only show networks on this band, in GHz
+    #[arg(long, value_enum)]
+    band: Option<Band>,
+
+    /// Hide networks weaker than this signal, e.g. -70
+    #[arg(long, value_name = "DBM", allow_hyphen_values = true)]
+    min_signal: Option<f32>,
+
+    /// Only show the strongest access point of each SSID
+    #[arg(long)]
+    dedup: bool,
+
+    /// Only show the given number of strongest networks
+    #[arg(long, value_name = "N")]
+    top: Option<usize>,
+
+    /// Print at most this many rows, keeping the current order
+    #[arg(long, value_name = "N")]
+    limit: Option<usize>,
+
+    /// Add a column with the signal as a 0-100% quality
+    #[arg(long)]
+    percent: bool,
+
+    /// Add a column with the center frequency in MHz
+    #[arg(long)]
+    freq: bool,
+
+    /// Add a column telling whether the access point advertises WPS, only
+    /// the iw backend reports it
+    #[arg(long)]
+    wps: bool,
+
+    /// Draw the signal strength as bars in front of its label
+    #[arg(long)]
+    bars: bool,
+
+    /// Add a column with the access point manufacturer, looked up offline
+    #[arg(long)]
+    vendor: bool,
+
+    /// Add a column marking the networks NetworkManager has a profile for
+    #[arg(long)]
+    show_known: bool,
+
+    /// Only show these comma-separated columns, in this order. In the table
+    /// `quality` is the percentage column, in JSON and CSV the quality label
+    #[arg(long, value_enum, value_delimiter = ',')]
+    fields: Vec<Field>,
+
+    /// Leave out the header line of the table, CSV and TSV output, to
+    /// append several scans to one file
+    #[arg(long)]
+    no_header: bool,
+
+    /// Only print the number of networks found
+    #[arg(long, conflicts_with_all = ["format", "json", "csv"])]
+    count: bool,
+
+    /// Rescan every given number of seconds until interrupted
+    #[arg(long, value_name = "SECONDS", value_parser = clap::value_parser!(u64).range(1..))]
+    watch: Option<u64>,
+
+    /// Alert while watching when this network drops below `--alert-below`
+    #[arg(long, value_name = "SSID", requires_all = ["watch", "alert_below"])]
+    alert_ssid: Option<String>,
+
+    /// Signal floor of `--alert-ssid`, e.g. -70
+    #[arg(
+        long,
+        value_name = "DBM",
+        allow_hyphen_values = true,
+        requires = "alert_ssid"
+    )]
+    alert_below: Option<f32>,
+
+    /// Exit with code 6 once the alert fires
+    #[arg(long, requires = "alert_ssid")]
+    alert_exit: bool,
+
+    /// Replace the last three octets of every BSSID with `xx`, to share a
+    /// scan without exposing the exact access points
+    #[arg(long, conflicts_with = "delta")]
+    mask_mac: bool,
+
+    /// Show how much each signal changed since the previous `--delta` scan
+    #[arg(long)]
+    delta: bool,
+
+    /// Only print ASCII: a plain connected marker and non-ASCII SSID bytes
+    /// escaped as \xNN, for logs and serial consoles
+    #[arg(long)]
+    ascii: bool,
+}
+
+impl ScanArgs {
+    fn format(&self) -> Format {
+        if self.json {
+            Format::Json
+        } else if self.csv {
+            Format::Csv
+        } else {
+            self.format
+        }
+    }
+
+    fn table_fields(&self) -> Vec<Field> {
+        if !self.fields.is_empty() {
+            return self.fields.clone();
+        }
+
+        let mut fields = vec![Field::Mac];
+        if self.all_interfaces {
+            fields.push(Field::Interface);
+        }
+        if self.vendor {
+            fields.push(Field::Vendor);
+        }
+        fields.extend([Field::Ssid, Field::Channel]);
+        if self.freq {
+            fields.push(Field::Frequency);
+        }
+        fields.extend([Field::Band, Field::Signal]);
+        if self.percent {
+            fields.push(Field::Quality);
+        }
+        fields.push(Field::Security);
+        if self.wps {
+            fields.push(Field::Wps);
+        }
+        if self.show_known {
+            fields.push(Field::Saved);
+        }
+
+        fields
+    }
+
+    fn csv_fields(&self) -> Vec<Field> {
+        if !self.fields.is_empty() {
+            return self.fields.clone();
+        }
+
+        // New columns go last, positional consumers rely on the others.
+        vec![
+            Field::Mac,
+            Field::Ssid,
+            Field::Channel,
+            Field::Signal,
+            Field::Security,
+            Field::Quality,
+            Field::Hidden,
         ]
     }
+
+    /// Whether the saved profiles have to be looked up for the output
+    fn wants_saved(&self) -> bool {
+        self.show_known || self.fields.contains(&Field::Saved)
+    }
+
+    fn is_filtered(&self) -> bool {
+        self.filter.is_some()
+            || self.open_only
+            || self.usable
+            || self.band.is_some()
+            || self.min_signal.is_some()
+    }
 }
 
-fn is_connected(ssid: &str) -> bool {
-    let nmcli = Command::new("nmcli")
-        .args(&["-t", "-f", "active,ssid", "dev", "wifi"])
-        .output()
-        .expect("failed to run nmcli");
+#[derive(Clone, Copy, Debug, PartialEq, ValueEnum)]
+enum Format {
+    /// Human readable table
+    Table,
+    /// Array of objects, one object per line with --watch
+    Json,
+    Csv,
+    /// Tab separated, tabs and newlines inside values become spaces
+    Tsv,
+    /// Array of [[network]] tables
+    Toml,
+}
 
-    let ssid_comp: String = "yes:".to_owned() + ssid;
-    let output = String::from_utf8_lossy(&nmcli.stdout);
-    let output = output.split('\n').take(1).collect::<Vec<_>>()[0];
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum BackendKind {
+    /// nmcli if installed, iw otherwise
+    Auto,
+    /// NetworkManager
+    Nmcli,
+    /// iw, for systems without NetworkManager
+    Iw,
+    /// The wifiscanner crate, iw on Linux
+    Wifiscanner,
+}
 
-    output.to_string().trim().starts_with("yes") && ssid_comp.eq(&output.to_string().trim())
+impl BackendKind {
+    fn backend(self, interface: Option<String>) -> Box<dyn ScanBackend> {
+        match self {
+            BackendKind::Auto => detect_backend_on(interface),
+            BackendKind::Nmcli => Box::new(NmcliBackend { interface }),
+            BackendKind::Iw => Box::new(IwBackend { interface }),
+            BackendKind::Wifiscanner => Box::new(WifiScannerBackend),
+        }
+    }
 }
 
-#[allow(non_snake_case)]
-fn dBm_signal_measure(signal: f32) -> SignalMeasure {
-    if signal >= -30.00 {
-        SignalMeasure::Maximum
-    } else if signal >= -50.00 {
-        SignalMeasure::Excellent
-    } else if signal >= -60.00 {
-        SignalMeasure::Good
-    } else if signal >= -67.00 {
-        SignalMeasure::Reliable
-    } else if signal >= -70.00 {
-        SignalMeasure::Weak
-    } else if signal >= -80.00 {
-        SignalMeasure::Unreliable
-    } else {
-        SignalMeasure::Bad
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum Band {
+    #[value(name = "2.4")]
+    TwoPointFour,
+    #[value(name = "5")]
+    Five,
+    #[value(name = "6")]
+    Six,
+}
+
+impl Band {
+    /// The band as named by `channel_to_band`
+    fn name(self) -> &'static str {
+        match self {
+            Band::TwoPointFour => "2.4GHz",
+            Band::Five => "5GHz",
+            Band::Six => "6GHz",
+        }
     }
 }
 
-fn is_root() -> bool {
-    match env::var("USER") {
-        Err(e) => {
-            println!("Something went very wrong: {:?}", e);
-            false
+#[derive(Clone, Copy, Debug, PartialEq, ValueEnum)]
+enum Field {
+    Mac,
+    Ssid,
+    Channel,
+    Signal,
+    Security,
+    Band,
+    Quality,
+    Vendor,
+    /// Whether a saved profile exists for the SSID
+    Saved,
+    /// The interface that heard the network, with `--all-interfaces`
+    Interface,
+    /// Whether the network hides its SSID
+    Hidden,
+    /// Center frequency in MHz
+    Frequency,
+    /// Whether the access point advertises WPS
+    Wps,
+}
+
+impl Field {
+    /// Name of the field in JSON and CSV output
+    fn key(self) -> &'static str {
+        match self {
+            Field::Mac => "mac",
+            Field::Ssid => "ssid",
+            Field::Channel => "channel",
+            Field::Signal => "signal_level",
+            Field::Security => "security",
+            Field::Band => "band",
+            Field::Quality => "quality",
+            Field::Vendor => "vendor",
+            Field::Saved => "saved",
+            Field::Interface => "interface",
+            Field::Hidden => "is_hidden",
+            Field::Frequency => "frequency",
+            Field::Wps => "wps",
         }
-        Ok(name) => {
-            if name != "root" {
-                println!("{}", "You must be root!".red().bold().blink());
-                false
-            } else {
-                true
-            }
+    }
+
+    fn title(self) -> &'static str {
+        match self {
+            Field::Mac => "Mac",
+            Field::Ssid => "SSID",
+            Field::Channel => "Channel",
+            Field::Signal => "Signal",
+            Field::Security => "Security",
+            Field::Band => "Band",
+            Field::Quality => "Quality",
+            Field::Vendor => "Vendor",
+            Field::Saved => "Saved",
+            Field::Interface => "Interface",
+            Field::Hidden => "Hidden",
+            Field::Frequency => "Freq",
+            Field::Wps => "WPS",
         }
     }
 }
 
-fn scan() -> Result<(), String> {
-    let networks = wifiscanner::scan().expect("Cannot scan network");
-    let mut networks_table = Table::new();
-    networks_table.set_format(*format::consts::FORMAT_CLEAN);
-    networks_table.add_row(row!["Mac", "SSID", "Channel", "Signal", "Security"]);
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum QrSecurity {
+    /// WPA, WPA2 or WPA3 personal
+    Wpa,
+    Wep,
+    /// Open network
+    Nopass,
+}
 
-    for network in networks {
-        networks_table.add_row(scan_table_format(&network));
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum RadioState {
+    On,
+    Off,
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum TableStyle {
+    /// Columns aligned with spaces only
+    Clean,
+    /// ASCII borders around every cell
+    Grid,
+    /// A GitHub flavored markdown table
+    Markdown,
+    /// Column separators and a line under the header
+    Borderless,
+}
+
+impl TableStyle {
+    fn format(self) -> format::TableFormat {
+        match self {
+            TableStyle::Clean => *format::consts::FORMAT_CLEAN,
+            TableStyle::Grid => *format::consts::FORMAT_DEFAULT,
+            // prettytable has no markdown preset.
+            TableStyle::Markdown => format::FormatBuilder::new()
+                .column_separator('|')
+                .borders('|')
+                .separator(
+                    format::LinePosition::Title,
+                    format::LineSeparator::new('-', '|', '|', '|'),
+                )
+                .padding(1, 1)
+                .build(),
+            TableStyle::Borderless => *format::consts::FORMAT_NO_BORDER_LINE_SEPARATOR,
+        }
     }
-    networks_table.printstd();
+}
 
-    Ok(())
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum LinkAction {
+    Up,
+    Down,
 }
 
-fn connect(ssid: &str, password: &str, interface: &str) -> Result<(), String> {
-    let config = Some(Config {
-        interface: Some(interface),
-    });
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum SortKey {
+    /// Strongest signal first
+    Signal,
+    /// Lowest channel first
+    Channel,
+    /// Alphabetical SSID
+    Ssid,
+    /// Alphabetical security
+    Security,
+    /// Alphabetical vendor, the unknown ones last by MAC
+    Vendor,
+}
 
-    let mut wifi = WiFi::new(config);
-    println!("Connection Status: {:?}", wifi.connect(ssid, password));
+#[derive(Debug, Serialize)]
+struct NetworkRecord<'a> {
+    mac: &'a str,
+    ssid: &'a str,
+    is_hidden: bool,
+    channel: u32,
+    /// MHz, null when neither the frequency nor the channel is known
+    frequency: Option<u32>,
+    band: &'static str,
+    signal_level: f32,
+    security: String,
+    /// Null when the backend does not report it
+    wps: Option<bool>,
+    quality: SignalMeasure,
+    vendor: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    saved: Option<bool>,
+    #[serde(skip_serializing_if = "str::is_empty")]
+    interface: &'a str,
+}
 
-    Ok(())
+impl<'a> NetworkRecord<'a> {
+    fn new(
+        network_info: &'a Network,
+        saved: Option<&HashSet<String>>,
+        config: &config::Config,
+    ) -> Self {
+        NetworkRecord {
+            mac: &network_info.mac,
+            ssid: &network_info.ssid,
+            is_hidden: network_info.is_hidden(),
+            channel: network_info.channel,
+            frequency: network_info.center_frequency(),
+            band: network_info.band(),
+            signal_level: network_info.signal_dbm,
+            security: network_info.security.to_string(),
+            wps: network_info.wps,
+            quality: network_info.quality(&config.thresholds),
+            vendor: network_info.vendor().unwrap_or("Unknown"),
+            saved: saved.map(|saved| saved.contains(&network_info.ssid)),
+            interface: &network_info.interface,
+        }
+    }
 }
 
-fn main() -> Result<(), String> {
-    let cli = Cli::parse();
+static BLINK: AtomicBool = AtomicBool::new(true);
 
-    match &cli.command {
-        Some(Commands::Scan {}) => {
-            if !is_root() {
-                exit(2);
-            }
-            scan()
+static QUIET: AtomicBool = AtomicBool::new(false);
+
+/// `println!` for the informational lines `--quiet` silences, errors and
+/// the output a command was asked for are always printed.
+macro_rules! inform {
+    ($($arg:tt)*) => {
+        if !QUIET.load(Ordering::Relaxed) {
+            println!($($arg)*);
         }
-        Some(Commands::Connect {
-            ssid,
-            password,
-            interface,
-        }) => {
-            if !is_root() {
-                exit(2);
+    };
+}
+
+/// Blinking is broken or distracting on many terminals, so `--no-blink`
+/// turns it off everywhere.
+fn blink(text: ColoredString) -> ColoredString {
+    if BLINK.load(Ordering::Relaxed) {
+        text.blink()
+    } else {
+        text
+    }
+}
+
+fn scan_table_header(args: &ScanArgs) -> Row {
+    args.table_fields()
+        .iter()
+        .map(|field| Cell::new(field.title()))
+        .collect()
+}
+
+/// The reverse of `decode_ssid`, every non-ASCII byte becomes `\xNN`.
+fn escape_non_ascii(text: &str) -> String {
+    text.bytes()
+        .map(|byte| match byte {
+            0x20..=0x7e => (byte as char).to_string(),
+            _ => format!("\\x{:02x}", byte),
+        })
+        .collect()
+}
+
+/// e.g. "↑ +3.00", changes under half a dB are shown as steady.
+fn signal_delta(signal: f32, previous: f32, ascii: bool) -> String {
+    let delta = signal - previous;
+    let (rising, falling, steady) = if ascii {
+        ("^", "v", "=")
+    } else {
+        ("\u{2191}", "\u{2193}", "\u{2192}")
+    };
+
+    if delta >= 0.5 {
+        format!("{} {:+.2}", rising.green(), delta)
+    } else if delta <= -0.5 {
+        format!("{} {:+.2}", falling.red(), delta)
+    } else {
+        format!("{} {:+.2}", steady, delta)
+    }
+}
+
+/// Four bars growing with `dbm_to_percent`, padded with blanks so every
+/// row is as wide.
+fn signal_bars(dbm: f32, ascii: bool) -> String {
+    let bars = if ascii {
+        ['#', '#', '#', '#']
+    } else {
+        ['\u{2582}', '\u{2584}', '\u{2586}', '\u{2588}']
+    };
+    let blank = if ascii { '.' } else { ' ' };
+    let lit = usize::from(dbm_to_percent(dbm)).div_ceil(25);
+
+    bars.iter()
+        .enumerate()
+        .map(|(i, &bar)| if i < lit { bar } else { blank })
+        .collect()
+}
+
+/// Whether the channel of `network` falls outside the ranges `domain`
+/// allows, false when either is unknown.
+fn outside_domain(network: &Network, domain: Option<&RegulatoryDomain>) -> bool {
+    match (domain, network.center_frequency()) {
+        (Some(domain), Some(frequency)) => !domain.allows(frequency),
+        _ => false,
+    }
+}
+
+fn scan_table_format(
+    network_info: &Network,
+    connected: &HashSet<String>,
+    saved: Option<&HashSet<String>>,
+    previous: Option<&SignalHistory>,
+    domain: Option<&RegulatoryDomain>,
+    args: &ScanArgs,
+    config: &config::Config,
+) -> Row {
+    // Pad the label before coloring it, the escape codes would otherwise
+    // count towards the width and the dBm would not line up.
+    let measure = network_info.quality(&config.thresholds);
+    let label = format!(
+        "{:<width$}",
+        config.labels.label(measure),
+        width = config.labels.width()
+    );
+    let label = match measure {
+        SignalMeasure::Maximum | SignalMeasure::Excellent => blink(label.green().bold()),
+        SignalMeasure::Good => blink(label.green()),
+        SignalMeasure::Reliable => blink(label.yellow().bold()),
+        SignalMeasure::Weak => label.yellow(),
+        SignalMeasure::Unreliable => label.red(),
+        SignalMeasure::Bad => label.red().bold(),
+    };
+    // An unparseable signal has no quality worth a label.
+    let mut signal_level = if network_info.signal_dbm.is_nan() {
+        "?".to_string()
+    } else {
+        format!("{} {:>7.2}", label, network_info.signal_dbm)
+    };
+    if args.bars {
+        signal_level = format!(
+            "{} {}",
+            signal_bars(network_info.signal_dbm, args.ascii),
+            signal_level
+        );
+    }
+    let signal_level = match previous.and_then(|p| p.signals.get(&network_info.mac)) {
+        Some(&before) if !network_info.signal_dbm.is_nan() => format!(
+            "{} {}",
+            signal_level,
+            signal_delta(network_info.signal_dbm, before, args.ascii)
+        ),
+        _ => signal_level,
+    };
+
+    let mac = if connected.contains(&network_info.ssid) && args.ascii {
+        format!("* {}", network_info.mac)
+    } else if connected.contains(&network_info.ssid) {
+        format!("{} {}", blink("*".green().bold()), network_info.mac)
+    } else {
+        network_info.mac.clone()
+    };
+
+    let label = network_info.security.to_string();
+    let security = match network_info.security.risk() {
+        Risk::High => label.red(),
+        Risk::Medium => label.yellow(),
+        Risk::Low => label.green(),
+        Risk::Unknown => label.normal(),
+    };
+
+    args.table_fields()
+        .iter()
+        .map(|field| match field {
+            Field::Mac => Cell::new(&mac),
+            Field::Ssid if network_info.is_hidden() => Cell::new(&"<hidden>".dimmed().to_string()),
+            Field::Ssid if args.ascii => Cell::new(
+                &escape_non_ascii(&network_info.ssid)
+                    .yellow()
+                    .bold()
+                    .to_string(),
+            ),
+            Field::Ssid => Cell::new(&network_info.ssid.yellow().bold().to_string()),
+            Field::Channel if outside_domain(network_info, domain) => Cell::new(
+                &format!("{}!", network_info.channel)
+                    .red()
+                    .bold()
+                    .to_string(),
+            ),
+            Field::Channel => {
+                Cell::new(&network_info.channel.to_string().white().bold().to_string())
+            }
+            Field::Frequency => match network_info.center_frequency() {
+                Some(frequency) => Cell::new(&format!("{} MHz", frequency)),
+                None => Cell::new("?"),
+            },
+            Field::Wps => match network_info.wps {
+                Some(true) => Cell::new("yes"),
+                Some(false) => Cell::new("no"),
+                None => Cell::new("?"),
+            },
+            Field::Band => Cell::new(network_info.band()),
+            Field::Signal => Cell::new(&signal_level),
+            Field::Quality => Cell::new(&format!("{}%", dbm_to_percent(network_info.signal_dbm))),
+            Field::Security => Cell::new(&security.to_string()),
+            Field::Vendor => Cell::new(network_info.vendor().unwrap_or("Unknown")),
+            Field::Interface => Cell::new(&network_info.interface),
+            Field::Hidden if network_info.is_hidden() => Cell::new("hidden"),
+            Field::Hidden => Cell::new(""),
+            Field::Saved => match saved {
+                Some(saved) if saved.contains(&network_info.ssid) => {
+                    Cell::new(&"saved".green().to_string())
+                }
+                _ => Cell::new(""),
+            },
+        })
+        .collect()
+}
+
+#[derive(Debug)]
+struct ActiveConnection {
+    ssid: String,
+    interface: String,
+    /// nmcli reports the signal as a 0-100 quality, this is its dBm
+    /// estimate, NaN when nmcli left it empty
+    signal: f32,
+}
+
+fn active_connection() -> Result<Option<ActiveConnection>, String> {
+    let nmcli =
+        run(Command::new("nmcli").args(["-t", "-f", "active,ssid,signal,device", "dev", "wifi"]))
+            .map_err(|e| format!("failed to run nmcli: {}", e))?;
+
+    let output = String::from_utf8_lossy(&nmcli.stdout);
+    Ok(output
+        .lines()
+        .find_map(|line| match split_terse(line).as_slice() {
+            [active, ssid, signal, interface] if active == "yes" => Some(ActiveConnection {
+                ssid: ssid.clone(),
+                interface: interface.clone(),
+                signal: parse_signal(signal).map_or(f32::NAN, percent_to_dbm),
+            }),
+            _ => None,
+        }))
+}
+
+/// The IPv4 addresses of `interface` with their prefix, e.g.
+/// "192.168.1.20/24", empty until DHCP handed one out.
+fn interface_addresses(interface: &str) -> Result<Vec<String>, String> {
+    let nmcli = run(Command::new("nmcli").args(["-g", "IP4.ADDRESS", "device", "show", interface]))
+        .map_err(|e| format!("failed to run nmcli: {}", e))?;
+
+    // nmcli separates several addresses with " | ".
+    Ok(String::from_utf8_lossy(&nmcli.stdout)
+        .split(['|', '\n'])
+        .map(str::trim)
+        .filter(|address| !address.is_empty())
+        .map(String::from)
+        .collect())
+}
+
+/// An entry of `ip -j addr show`.
+#[derive(Debug, Deserialize)]
+struct IpInterface {
+    #[serde(default)]
+    addr_info: Vec<IpAddress>,
+}
+
+#[derive(Debug, Deserialize)]
+struct IpAddress {
+    family: String,
+    local: String,
+    prefixlen: u8,
+}
+
+/// An entry of `ip -j route show`.
+#[derive(Debug, Deserialize)]
+struct IpRoute {
+    gateway: Option<String>,
+}
+
+fn ip_json<T: DeserializeOwned>(ip: &Path, args: &[&str]) -> Result<Vec<T>, String> {
+    let output =
+        run(Command::new(ip).args(args)).map_err(|e| format!("failed to run ip: {}", e))?;
+    if !output.status.success() {
+        return Err(String::from_utf8_lossy(&output.stderr).trim().to_string());
+    }
+
+    serde_json::from_slice(&output.stdout).map_err(|e| format!("cannot parse ip output: {}", e))
+}
+
+/// The first IPv4 address of `interface` with its prefix, and its default
+/// gateway.
+fn ipv4_lease(ip: &Path, interface: &str) -> Result<(Option<String>, Option<String>), String> {
+    let address = ip_json::<IpInterface>(ip, &["-j", "addr", "show", "dev", interface])?
+        .into_iter()
+        .flat_map(|entry| entry.addr_info)
+        .find(|address| address.family == "inet")
+        .map(|address| format!("{}/{}", address.local, address.prefixlen));
+    let gateway = ip_json::<IpRoute>(ip, &["-j", "route", "show", "default", "dev", interface])?
+        .into_iter()
+        .find_map(|route| route.gateway);
+
+    Ok((address, gateway))
+}
+
+const LEASE_ATTEMPTS: u32 = 5;
+const LEASE_DELAY: Duration = Duration::from_secs(1);
+
+/// Print the address and gateway `interface` got once connected, giving
+/// DHCP a few seconds. Nothing is printed without `ip`.
+fn report_lease(interface: &str) {
+    let ip = match find_command("ip") {
+        Some(ip) => ip,
+        None => return,
+    };
+
+    for attempt in 1..=LEASE_ATTEMPTS {
+        match ipv4_lease(&ip, interface) {
+            Ok((Some(address), gateway)) => {
+                inform!("IP address: {}", address);
+                inform!("Gateway: {}", gateway.as_deref().unwrap_or("none"));
+                return;
+            }
+            Ok((None, _)) if attempt < LEASE_ATTEMPTS => thread::sleep(LEASE_DELAY),
+            Ok((None, _)) => inform!("no IP yet"),
+            Err(e) => {
+                eprintln!("cannot read the address of {}: {}", interface, e);
+                return;
             }
-            connect(ssid, password, interface)
         }
+    }
+}
+
+/// Association is not the end of it, wait until nmcli reports `ssid` as
+/// active on `interface` and the interface got an address.
+fn verify_connection(ssid: &str, interface: &str, timeout: Duration) -> ConnectOutcome {
+    let deadline = Instant::now() + timeout;
+    loop {
+        let active = match active_connection() {
+            Ok(active) => active,
+            Err(e) => return ConnectOutcome::Other(e),
+        };
+        if active.is_some_and(|a| a.ssid == ssid && a.interface == interface) {
+            match interface_addresses(interface) {
+                // The address is reported by `report_lease` afterwards.
+                Ok(addresses) if !addresses.is_empty() => return ConnectOutcome::Connected,
+                Ok(_) => {}
+                Err(e) => return ConnectOutcome::Other(e),
+            }
+        }
+        if Instant::now() >= deadline {
+            return ConnectOutcome::Other(format!(
+                "{} did not come up with an IP address within {} seconds",
+                interface,
+                timeout.as_secs()
+            ));
+        }
+        thread::sleep(Duration::from_secs(1));
+    }
+}
+
+static NMCLI_WARNED: AtomicBool = AtomicBool::new(false);
+
+/// SSIDs nmcli reports as active, gathered once per scan rather than
+/// spawning nmcli for every row. Without nmcli nothing is marked connected.
+fn connected_ssids() -> HashSet<String> {
+    let nmcli = match run(Command::new("nmcli").args(["-t", "-f", "active,ssid", "dev", "wifi"])) {
+        Ok(nmcli) => nmcli,
+        Err(e) => {
+            if !NMCLI_WARNED.swap(true, Ordering::Relaxed) {
+                eprintln!(
+                    "cannot run nmcli ({}), the connected network is not marked",
+                    e
+                );
+            }
+            return HashSet::new();
+        }
+    };
+
+    parse_active_ssids(&String::from_utf8_lossy(&nmcli.stdout))
+}
+
+/// The SSIDs of the `yes` lines of `nmcli -t -f active,ssid dev wifi`. A row
+/// is marked connected only when its SSID is one of these exactly, so
+/// `Home2` is never taken for `Home`. A hidden network has no SSID to tell
+/// it from the other hidden ones, it is left out.
+fn parse_active_ssids(output: &str) -> HashSet<String> {
+    output
+        .lines()
+        .filter_map(|line| match split_terse(line).as_slice() {
+            [active, ssid] if active == "yes" && !ssid.is_empty() => Some(ssid.clone()),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Unparseable signals sort as the weakest.
+fn signal_sort_key(network: &Network) -> f32 {
+    if network.signal_dbm.is_nan() {
+        f32::NEG_INFINITY
+    } else {
+        network.signal_dbm
+    }
+}
+
+/// Keep the strongest BSSID of every SSID, hidden networks are left alone
+/// as their empty SSIDs say nothing about them being the same network.
+fn dedup_by_ssid(networks: Vec<Network>) -> Vec<Network> {
+    let mut strongest: HashMap<String, usize> = HashMap::new();
+    let mut deduped: Vec<Network> = Vec::with_capacity(networks.len());
+
+    for network in networks {
+        if network.ssid.is_empty() {
+            deduped.push(network);
+            continue;
+        }
+        match strongest.get(&network.ssid) {
+            Some(&index) => {
+                if signal_sort_key(&network) > signal_sort_key(&deduped[index]) {
+                    deduped[index] = network;
+                }
+            }
+            None => {
+                strongest.insert(network.ssid.clone(), deduped.len());
+                deduped.push(network);
+            }
+        }
+    }
+
+    deduped
+}
+
+fn sort_networks(networks: &mut [Network], key: SortKey) {
+    match key {
+        SortKey::Signal => networks.sort_by(|a, b| {
+            signal_sort_key(b)
+                .total_cmp(&signal_sort_key(a))
+                .then_with(|| a.ssid.cmp(&b.ssid))
+        }),
+        SortKey::Channel => networks.sort_by_key(|n| match n.channel {
+            0 => u32::MAX,
+            channel => channel,
+        }),
+        SortKey::Ssid => networks.sort_by(|a, b| a.ssid.cmp(&b.ssid)),
+        SortKey::Security => networks.sort_by_key(|n| n.security.to_string()),
+        SortKey::Vendor => networks.sort_by(|a, b| {
+            let vendor = |n: &Network| (n.vendor().is_none(), n.vendor());
+            vendor(a).cmp(&vendor(b)).then_with(|| a.mac.cmp(&b.mac))
+        }),
+    }
+}
+
+fn is_root() -> bool {
+    // SAFETY: geteuid() has no preconditions and cannot fail.
+    if unsafe { libc::geteuid() } != 0 {
+        println!("{}", blink("You must be root!".red().bold()));
+        false
+    } else {
+        true
+    }
+}
+
+/// The records as JSON objects, keeping only the requested fields.
+fn network_values(
+    networks: &[Network],
+    fields: &[Field],
+    saved: Option<&HashSet<String>>,
+    config: &config::Config,
+) -> Result<Vec<serde_json::Map<String, serde_json::Value>>, String> {
+    networks
+        .iter()
+        .map(|network| {
+            let record = serde_json::to_value(NetworkRecord::new(network, saved, config))
+                .map_err(|e| e.to_string())?;
+            let mut record = match record {
+                serde_json::Value::Object(record) => record,
+                _ => unreachable!("a struct always serializes to an object"),
+            };
+            if fields.is_empty() {
+                return Ok(record);
+            }
+
+            // A field left out of the record, such as an empty interface,
+            // stays as a null so the CSV columns keep lining up.
+            Ok(fields
+                .iter()
+                .map(|field| {
+                    let value = record.remove(field.key()).unwrap_or_default();
+                    (field.key().to_string(), value)
+                })
+                .collect())
+        })
+        .collect()
+}
+
+/// Version of the `--json` scan output. Adding a field keeps it, renaming,
+/// removing or changing the type of one bumps it.
+const JSON_VERSION: u32 = 1;
+
+fn scan_json(networks: &[Network], args: &ScanArgs, config: &config::Config) -> Result<(), String> {
+    let saved = args.wants_saved().then(saved_ssids);
+    let networks = network_values(networks, &args.fields, saved.as_ref(), config)?;
+    let envelope = serde_json::json!({
+        "version": JSON_VERSION,
+        "networks": networks,
+    });
+    let output = serde_json::to_string_pretty(&envelope).map_err(|e| e.to_string())?;
+    println!("{}", output);
+
+    Ok(())
+}
+
+/// One compact `{"version": ..., "timestamp": ..., "networks": [...]}` line
+/// per scan of `--watch --json`, flushed so a consumer can follow the stream.
+fn scan_ndjson(
+    networks: &[Network],
+    args: &ScanArgs,
+    config: &config::Config,
+) -> Result<(), String> {
+    let saved = args.wants_saved().then(saved_ssids);
+    let event = serde_json::json!({
+        "version": JSON_VERSION,
+        "timestamp": Local::now().to_rfc3339(),
+        "networks": network_values(networks, &args.fields, saved.as_ref(), config)?,
+    });
+    let mut stdout = io::stdout().lock();
+    writeln!(stdout, "{}", event)
+        .and_then(|_| stdout.flush())
+        .map_err(|e| e.to_string())
+}
+
+/// Quote a CSV field following RFC 4180 when it contains a delimiter,
+/// a quote or a line break.
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+/// TSV has no quoting, the separators are blanked out instead.
+fn tsv_field(field: &str) -> String {
+    field.replace(['\t', '\n', '\r'], " ")
+}
+
+/// CSV, or TSV when `separator` is a tab.
+fn scan_delimited(
+    networks: &[Network],
+    args: &ScanArgs,
+    separator: &str,
+    config: &config::Config,
+) -> Result<(), String> {
+    let quote = if separator == "\t" {
+        tsv_field
+    } else {
+        csv_field
+    };
+    let fields = args.csv_fields();
+    if !args.no_header {
+        let header: Vec<&str> = fields.iter().map(|field| field.key()).collect();
+        println!("{}", header.join(separator));
+    }
+
+    let saved = args.wants_saved().then(saved_ssids);
+    for network in network_values(networks, &fields, saved.as_ref(), config)? {
+        let line: Vec<String> = network
+            .values()
+            .map(|value| match value {
+                serde_json::Value::String(value) => quote(value),
+                serde_json::Value::Null => String::new(),
+                value => value.to_string(),
+            })
+            .collect();
+        println!("{}", line.join(separator));
+    }
+
+    Ok(())
+}
+
+fn scan_toml(networks: &[Network], args: &ScanArgs, config: &config::Config) -> Result<(), String> {
+    let saved = args.wants_saved().then(saved_ssids);
+    let mut networks = network_values(networks, &args.fields, saved.as_ref(), config)?;
+    // TOML has no null, leave the unknown values out.
+    for network in &mut networks {
+        network.retain(|_, value| !value.is_null());
+    }
+
+    let mut document = serde_json::Map::new();
+    document.insert("network".to_string(), networks.into());
+    let output = toml::to_string(&document).map_err(|e| e.to_string())?;
+    print!("{}", output);
+
+    Ok(())
+}
+
+fn scan(args: &ScanArgs, config: &config::Config) -> Result<(), String> {
+    let interval = match args.watch {
+        Some(seconds) => Duration::from_secs(seconds),
+        None => return scan_once(args, config),
+    };
+
+    let json = args.format() == Format::Json;
+    ctrlc::set_handler(move || {
+        // Leave the terminal without any pending color or blink attribute,
+        // a JSON stream gets nothing but JSON.
+        if !json {
+            print!("\x1b[0m");
+        }
+        let _ = io::stdout().flush();
+        exit(0);
+    })
+    .map_err(|e| format!("cannot install the Ctrl-C handler: {}", e))?;
+
+    let mut alert = match (&args.alert_ssid, args.alert_below) {
+        (Some(ssid), Some(below)) => Some(SignalAlert {
+            ssid,
+            below,
+            dropped: false,
+        }),
+        _ => None,
+    };
+    loop {
+        let networks = gather_networks(args, config)?;
+        let fired = alert.as_mut().and_then(|alert| alert.check(&networks));
+        if json {
+            scan_ndjson(&networks, args, config)?;
+        } else {
+            print!("\x1b[2J\x1b[H");
+            println!("{}", Local::now().format("%Y-%m-%d %H:%M:%S"));
+            print_scan(networks, args, config)?;
+        }
+        // On stderr, the JSON stream stays clean.
+        if let Some(fired) = fired {
+            eprintln!("{}", fired.red().bold());
+            if args.alert_exit {
+                exit(6);
+            }
+        }
+        thread::sleep(interval);
+    }
+}
+
+/// The `--alert-ssid` of `scan --watch`, it fires once each time the
+/// network drops below its floor, not on every scan it stays there.
+struct SignalAlert<'a> {
+    ssid: &'a str,
+    below: f32,
+    /// Whether the previous scan was already below the floor
+    dropped: bool,
+}
+
+impl SignalAlert<'_> {
+    /// The alert line when the network just dropped below its floor. Only
+    /// the networks left by the filters count, one out of range is below
+    /// any floor.
+    fn check(&mut self, networks: &[Network]) -> Option<String> {
+        let signal = networks
+            .iter()
+            .filter(|n| n.ssid == self.ssid)
+            .map(signal_sort_key)
+            .fold(f32::NEG_INFINITY, f32::max);
+        let dropped = signal < self.below;
+        let fired = dropped && !self.dropped;
+        self.dropped = dropped;
+
+        if !fired {
+            None
+        } else if signal == f32::NEG_INFINITY {
+            Some(format!("ALERT: {} is out of range", self.ssid))
+        } else {
+            Some(format!(
+                "ALERT: {} dropped to {:.2} dBm, below {:.2} dBm",
+                self.ssid, signal, self.below
+            ))
+        }
+    }
+}
+
+fn scan_backend(args: &ScanArgs) -> Result<Vec<Network>, String> {
+    if args.all_interfaces {
+        scan_all_interfaces(args)
+    } else {
+        scan_with_rescan(args, args.interface.clone())
+    }
+}
+
+const SAMPLE_DELAY: Duration = Duration::from_secs(1);
+
+/// Scan `--samples` times and average the signal of every BSSID over the
+/// scans that heard it, one missing from some keeps its observed average.
+fn scan_samples(args: &ScanArgs) -> Result<Vec<Network>, String> {
+    let mut networks: Vec<Network> = Vec::new();
+    let mut signals: HashMap<String, (f32, u32)> = HashMap::new();
+
+    for sample in 0..args.samples {
+        if sample > 0 {
+            thread::sleep(SAMPLE_DELAY);
+        }
+        for network in scan_backend(args)? {
+            let (sum, count) = signals.entry(network.mac.clone()).or_insert((0.0, 0));
+            if !network.signal_dbm.is_nan() {
+                *sum += network.signal_dbm;
+                *count += 1;
+            }
+            if !networks.iter().any(|n| n.mac == network.mac) {
+                networks.push(network);
+            }
+        }
+    }
+    for network in &mut networks {
+        network.signal_dbm = match signals[&network.mac] {
+            (_, 0) => f32::NAN,
+            (sum, count) => sum / count as f32,
+        };
+    }
+
+    Ok(networks)
+}
+
+const RESCAN_ATTEMPTS: usize = 3;
+const RESCAN_DELAY: Duration = Duration::from_secs(2);
+
+/// Scan and apply every filter and the ordering asked for on the command line.
+fn gather_networks(args: &ScanArgs, config: &config::Config) -> Result<Vec<Network>, String> {
+    let started = Instant::now();
+    let mut networks = match &args.load {
+        Some(path) => load_scan(path)?,
+        None if args.samples > 1 => scan_samples(args)?,
+        None => scan_backend(args)?,
+    };
+    if args.timing {
+        // On stderr, the parseable formats stay untouched.
+        eprintln!(
+            "scanned {} networks in {} ms",
+            networks.len(),
+            started.elapsed().as_millis()
+        );
+    }
+    if let Some(filter) = &args.filter {
+        let filter = filter.to_lowercase();
+        networks.retain(|n| n.ssid.to_lowercase().contains(&filter));
+    }
+    if args.open_only {
+        networks.retain(Network::is_open);
+    }
+    if args.usable {
+        let saved = saved_ssids();
+        networks.retain(|n| {
+            (n.is_open() || saved.contains(&n.ssid))
+                && matches!(
+                    n.quality(&config.thresholds),
+                    SignalMeasure::Maximum
+                        | SignalMeasure::Excellent
+                        | SignalMeasure::Good
+                        | SignalMeasure::Reliable
+                )
+        });
+    }
+    if let Some(band) = args.band {
+        // Unknown channels band as "?", which never matches.
+        networks.retain(|n| n.band() == band.name());
+    }
+    if let Some(min_signal) = args.min_signal {
+        // An unparseable (NaN) signal never passes the threshold.
+        networks.retain(|n| n.signal_dbm >= min_signal);
+    }
+    if args.dedup {
+        networks = dedup_by_ssid(networks);
+    }
+    if let Some(top) = args.top {
+        sort_networks(&mut networks, SortKey::Signal);
+        if args.reverse {
+            networks.reverse();
+        }
+        networks.truncate(top);
+    }
+    sort_networks(&mut networks, args.sort);
+    if args.reverse {
+        networks.reverse();
+    }
+    if let Some(path) = &args.save {
+        save_scan(path, &networks)?;
+    }
+    if args.mask_mac {
+        for network in &mut networks {
+            network.mac = mask_mac(&network.mac);
+        }
+    }
+
+    Ok(networks)
+}
+
+/// Drivers often come back empty from the first scan after the radio came
+/// up, `--rescan` retries a few times before believing them.
+fn scan_with_rescan(args: &ScanArgs, interface: Option<String>) -> Result<Vec<Network>, String> {
+    let mut networks = scan_with_timeout(args, interface.clone())?;
+    if args.rescan {
+        for _ in 0..RESCAN_ATTEMPTS {
+            if !networks.is_empty() {
+                break;
+            }
+            thread::sleep(RESCAN_DELAY);
+            networks = scan_with_timeout(args, interface.clone())?;
+        }
+    }
+
+    Ok(networks)
+}
+
+/// A wedged driver can block a scan forever, with `--timeout` the scan runs
+/// in a thread that is abandoned once the time is up.
+fn scan_with_timeout(args: &ScanArgs, interface: Option<String>) -> Result<Vec<Network>, String> {
+    let timeout = match args.timeout {
+        Some(seconds) => Duration::from_secs(seconds),
+        None => return Ok(args.backend.backend(interface).scan()?),
+    };
+
+    let kind = args.backend;
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || {
+        // The receiver is gone when the scan took too long.
+        let _ = sender.send(kind.backend(interface).scan().map_err(String::from));
+    });
+
+    match receiver.recv_timeout(timeout) {
+        Ok(networks) => networks,
+        Err(_) => Err("scan timed out".to_string()),
+    }
+}
+
+/// Scan with every wireless interface, a BSSID heard by several radios is
+/// kept once with its strongest signal.
+fn scan_all_interfaces(args: &ScanArgs) -> Result<Vec<Network>, String> {
+    let mut strongest: HashMap<String, Network> = HashMap::new();
+    for interface in wireless_interfaces()? {
+        for mut network in scan_with_rescan(args, Some(interface.clone()))? {
+            network.interface = interface.clone();
+            match strongest.get(&network.mac) {
+                Some(seen) if signal_sort_key(seen) >= signal_sort_key(&network) => {}
+                _ => {
+                    strongest.insert(network.mac.clone(), network);
+                }
+            }
+        }
+    }
+
+    Ok(strongest.into_values().collect())
+}
+
+/// Keep the OUI of a BSSID, the vendor part, and hide the rest as
+/// `xx:xx:xx`.
+fn mask_mac(mac: &str) -> String {
+    match mac.get(..8) {
+        Some(oui) if mac.len() > 8 => format!("{}:xx:xx:xx", oui),
+        _ => mac.to_string(),
+    }
+}
+
+fn save_scan(path: &Path, networks: &[Network]) -> Result<(), String> {
+    let contents = serde_json::to_string_pretty(networks).map_err(|e| e.to_string())?;
+
+    fs::write(path, contents).map_err(|e| format!("cannot save scan to {}: {}", path.display(), e))
+}
+
+fn load_scan(path: &Path) -> Result<Vec<Network>, String> {
+    let contents = fs::read_to_string(path)
+        .map_err(|e| format!("cannot read scan {}: {}", path.display(), e))?;
+
+    serde_json::from_str(&contents).map_err(|e| format!("invalid scan {}: {}", path.display(), e))
+}
+
+/// e.g. "Found 23 networks (18 secured, 5 open) across 2.4GHz and 5GHz",
+/// the networks the backend reported no security for are counted apart.
+fn scan_summary(networks: &[Network]) -> String {
+    let open = networks.iter().filter(|n| n.is_open()).count();
+    let unknown = networks
+        .iter()
+        .filter(|n| matches!(n.security, Security::Unknown(_)))
+        .count();
+    let bands: Vec<&str> = ["2.4GHz", "5GHz", "6GHz"]
+        .iter()
+        .copied()
+        .filter(|band| networks.iter().any(|n| n.band() == *band))
+        .collect();
+
+    let mut counts = format!("{} secured, {} open", networks.len() - open - unknown, open);
+    if unknown > 0 {
+        counts.push_str(&format!(", {} unknown", unknown));
+    }
+    let mut summary = format!(
+        "Found {} network{} ({})",
+        networks.len(),
+        if networks.len() == 1 { "" } else { "s" },
+        counts
+    );
+    if let Some((last, rest)) = bands.split_last() {
+        summary.push_str(" across ");
+        if !rest.is_empty() {
+            summary.push_str(&rest.join(", "));
+            summary.push_str(" and ");
+        }
+        summary.push_str(last);
+    }
+
+    summary
+}
+
+fn scan_once(args: &ScanArgs, config: &config::Config) -> Result<(), String> {
+    print_scan(gather_networks(args, config)?, args, config)
+}
+
+fn print_scan(
+    mut networks: Vec<Network>,
+    args: &ScanArgs,
+    config: &config::Config,
+) -> Result<(), String> {
+    if args.count {
+        println!("{}", networks.len());
+        return Ok(());
+    }
+    match args.format() {
+        Format::Table => {}
+        Format::Json => return scan_json(&networks, args, config),
+        Format::Csv => return scan_delimited(&networks, args, ",", config),
+        Format::Tsv => return scan_delimited(&networks, args, "\t", config),
+        Format::Toml => return scan_toml(&networks, args, config),
+    }
+
+    // A lone header row would look like the scan broke, the JSON output
+    // has its empty `networks` array instead.
+    if networks.is_empty() && !args.is_filtered() {
+        println!("No wireless networks found.");
+        return Ok(());
+    }
+
+    // The connection of today says nothing about a scan loaded from a file.
+    let connected = if args.load.is_some() {
+        HashSet::new()
+    } else {
+        connected_ssids()
+    };
+    if args.connected_first {
+        // Stable, the rest keeps the --sort order.
+        networks.sort_by_key(|n| !connected.contains(&n.ssid));
+    }
+    let saved = args.wants_saved().then(saved_ssids);
+    let previous = if args.delta {
+        Some(SignalHistory::load()?)
+    } else {
+        None
+    };
+    let domain = regulatory_domain();
+    let limit = args.limit.unwrap_or(networks.len());
+    let shown: Vec<&Network> = networks.iter().take(limit).collect();
+    // One sub-table per band with `--group-by-band`, each keeping the
+    // order of the networks.
+    let groups: Vec<(Option<&str>, Vec<&Network>)> = if args.group_by_band {
+        ["2.4GHz", "5GHz", "6GHz", "?"]
+            .iter()
+            .map(|&band| {
+                let group = shown.iter().copied().filter(|n| n.band() == band);
+                (Some(band), group.collect::<Vec<_>>())
+            })
+            .filter(|(_, group)| !group.is_empty())
+            .collect()
+    } else {
+        vec![(None, shown)]
+    };
+    for (band, group) in groups {
+        match band {
+            Some("?") => println!("{}", "Unknown band".bold()),
+            Some(band) => println!("{}", band.bold()),
+            None => {}
+        }
+        let mut networks_table = Table::new();
+        networks_table.set_format(args.table_style.format());
+        if !args.no_header {
+            networks_table.set_titles(scan_table_header(args));
+        }
+        for network in group {
+            networks_table.add_row(scan_table_format(
+                network,
+                &connected,
+                saved.as_ref(),
+                previous.as_ref(),
+                domain.as_ref(),
+                args,
+                config,
+            ));
+        }
+        networks_table.printstd();
+    }
+
+    if let Some(mut history) = previous {
+        // Merge rather than replace, so a filtered scan keeps the signals
+        // of the networks it hid.
+        history.signals.extend(
+            networks
+                .iter()
+                .filter(|n| !n.signal_dbm.is_nan())
+                .map(|n| (n.mac.clone(), n.signal_dbm)),
+        );
+        if let Err(e) = history.save() {
+            eprintln!("cannot remember the signals for --delta: {}", e);
+        }
+    }
+
+    if networks.len() > limit {
+        println!("... and {} more", networks.len() - limit);
+    }
+
+    if networks.is_empty() && args.is_filtered() {
+        println!("no matching networks");
+    }
+    if !networks.is_empty() {
+        inform!("{}", scan_summary(&networks));
+    }
+    if let Some(domain) = &domain {
+        if networks
+            .iter()
+            .take(limit)
+            .any(|n| outside_domain(n, Some(domain)))
+        {
+            inform!(
+                "Channels marked ! are not allowed in the {} regulatory domain",
+                domain.country
+            );
+        }
+    }
+
+    Ok(())
+}
+
+fn read_password(ssid: &str) -> Result<String, String> {
+    if !io::stdin().is_terminal() {
+        return Err("no --password given and stdin is not a terminal".to_string());
+    }
+
+    rpassword::prompt_password(format!("Password for {}: ", ssid))
+        .map_err(|e| format!("cannot read password: {}", e))
+}
+
+/// Only the line ending is dropped, a password may end in spaces.
+fn trim_newline(line: &str) -> &str {
+    line.trim_end_matches(['\r', '\n'])
+}
+
+/// The traffic of an open network is not encrypted, make sure joining one
+/// is not an accident. Without a terminal to ask on, `--yes` is required.
+fn confirm_open(ssid: &str) -> Result<(), String> {
+    eprintln!(
+        "{} {} is an open network, its traffic is not encrypted",
+        "Warning:".yellow().bold(),
+        ssid
+    );
+    if !io::stdin().is_terminal() {
+        return Err("refusing to join an open network without --yes".to_string());
+    }
+
+    eprint!("Connect anyway? [y/N] ");
+    let mut answer = String::new();
+    io::stdin()
+        .read_line(&mut answer)
+        .map_err(|e| format!("cannot read the answer: {}", e))?;
+    match answer.trim().to_lowercase().as_str() {
+        "y" | "yes" => Ok(()),
+        _ => Err("not connecting".to_string()),
+    }
+}
+
+fn read_password_stdin() -> Result<String, String> {
+    let mut line = String::new();
+    io::stdin()
+        .read_line(&mut line)
+        .map_err(|e| format!("cannot read password from stdin: {}", e))?;
+
+    Ok(trim_newline(&line).to_string())
+}
+
+fn read_password_file(path: &Path) -> Result<String, String> {
+    let contents = fs::read_to_string(path)
+        .map_err(|e| format!("cannot read password file {}: {}", path.display(), e))?;
+
+    Ok(contents.lines().next().unwrap_or_default().to_string())
+}
+
+/// How an attempt to join a network ended.
+#[derive(Debug, PartialEq)]
+enum ConnectOutcome {
+    Connected,
+    /// The password is wrong, or missing for a secured network
+    AuthFailed,
+    /// No network with the SSID is in range
+    NotFound,
+    Timeout,
+    Other(String),
+}
+
+impl ConnectOutcome {
+    /// Classify the stderr of a failed `nmcli device wifi connect`.
+    fn from_nmcli_error(stderr: &str) -> ConnectOutcome {
+        if stderr.contains("Secrets were required") || stderr.contains("password") {
+            ConnectOutcome::AuthFailed
+        } else if stderr.contains("No network with SSID") {
+            ConnectOutcome::NotFound
+        } else if stderr.contains("Timeout") || stderr.contains("timed out") {
+            ConnectOutcome::Timeout
+        } else {
+            ConnectOutcome::Other(stderr.trim().to_string())
+        }
+    }
+
+    /// 2 is taken by the not-root exit, 1 by every other error.
+    fn exit_code(&self) -> i32 {
+        match self {
+            ConnectOutcome::Connected => 0,
+            ConnectOutcome::Other(_) => 1,
+            ConnectOutcome::AuthFailed => 3,
+            ConnectOutcome::NotFound => 4,
+            ConnectOutcome::Timeout => 5,
+        }
+    }
+}
+
+impl fmt::Display for ConnectOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ConnectOutcome::Connected => write!(f, "connected"),
+            ConnectOutcome::AuthFailed => write!(f, "authentication failed, check the password"),
+            ConnectOutcome::NotFound => write!(f, "no such network in range"),
+            ConnectOutcome::Timeout => write!(f, "timed out"),
+            ConnectOutcome::Other(reason) => write!(f, "{}", reason),
+        }
+    }
+}
+
+/// Exit with the code of a failed `outcome`, scripts can tell a wrong
+/// password from a network out of range.
+fn check_outcome(ssid: &str, outcome: ConnectOutcome) -> Result<(), String> {
+    if outcome == ConnectOutcome::Connected {
+        return Ok(());
+    }
+
+    eprintln!("Error: failed to connect to {}: {}", ssid, outcome);
+    exit(outcome.exit_code());
+}
+
+/// Join a network through nmcli, its stderr tells a wrong password from a
+/// network out of range or a timeout.
+fn connect_nmcli(
+    ssid: &str,
+    password: Option<&str>,
+    options: &JoinOptions,
+    interface: &str,
+) -> ConnectOutcome {
+    let nmcli = match run(&mut nmcli_connect_command(
+        ssid, password, options, interface,
+    )) {
+        Ok(nmcli) => nmcli,
+        Err(e) => return ConnectOutcome::Other(format!("failed to run nmcli: {}", e)),
+    };
+
+    if String::from_utf8_lossy(&nmcli.stdout).contains("successfully activated") {
+        ConnectOutcome::Connected
+    } else {
+        ConnectOutcome::from_nmcli_error(&String::from_utf8_lossy(&nmcli.stderr))
+    }
+}
+
+/// `nmcli device wifi connect`, pinned to an access point or saved under a
+/// profile name when asked.
+fn nmcli_connect_command(
+    ssid: &str,
+    password: Option<&str>,
+    options: &JoinOptions,
+    interface: &str,
+) -> Command {
+    let mut nmcli = Command::new("nmcli");
+    nmcli.args(["d", "wifi", "connect", ssid]);
+    if let Some(password) = password {
+        nmcli.args(["password", password]);
+    }
+    if let Some(bssid) = options.bssid {
+        nmcli.args(["bssid", bssid]);
+    }
+    if let Some(profile) = options.profile {
+        nmcli.args(["name", profile]);
+    }
+    nmcli.args(["ifname", interface]);
+
+    nmcli
+}
+
+/// A 40 or 104 bit WEP key, as 5 or 13 ASCII characters or 10 or 26 hex
+/// digits.
+fn is_wep_key(key: &str) -> bool {
+    match key.len() {
+        5 | 13 => key.is_ascii(),
+        10 | 26 => key.chars().all(|c| c.is_ascii_hexdigit()),
+        _ => false,
+    }
+}
+
+/// Catch what 802.11 would reject before it fails deep in nmcli: an SSID
+/// is 1-32 bytes, a WPA passphrase 8-63 characters or a 64 hex digit key.
+/// WEP keys are passed through, they are shorter.
+fn validate_credentials(ssid: &str, password: Option<&str>) -> Result<(), String> {
+    if ssid.is_empty() || ssid.len() > 32 {
+        return Err(format!(
+            "the SSID must be 1 to 32 bytes long, {} is {}",
+            ssid,
+            ssid.len()
+        ));
+    }
+
+    match password {
+        // An empty password joins the network as an open one.
+        Some("") => Ok(()),
+        Some(password) if is_wep_key(password) => Ok(()),
+        Some(password) if password.len() == 64 => {
+            if password.chars().all(|c| c.is_ascii_hexdigit()) {
+                Ok(())
+            } else {
+                Err("a 64 character password must be a hexadecimal key".to_string())
+            }
+        }
+        Some(password) if !(8..=63).contains(&password.chars().count()) => Err(format!(
+            "the password must be 8 to 63 characters long, not {}",
+            password.chars().count()
+        )),
+        _ => Ok(()),
+    }
+}
+
+fn connect(
+    ssid: &str,
+    password: Option<&str>,
+    options: &JoinOptions,
+    interface: &str,
+) -> ConnectOutcome {
+    let password = password.filter(|password| !password.is_empty());
+    let outcome = match (password, &options.enterprise) {
+        (_, Some(enterprise)) => {
+            connect_enterprise(ssid, password, enterprise, options.profile, interface)
+        }
+        _ => connect_nmcli(ssid, password, options, interface),
+    };
+    if outcome != ConnectOutcome::Connected {
+        return outcome;
+    }
+    inform!("Connected to {}", ssid);
+
+    let last = LastConnection {
+        ssid: ssid.to_string(),
+        interface: interface.to_string(),
+    };
+    if let Err(e) = last.save() {
+        eprintln!("cannot remember the connection for reconnect: {}", e);
+    }
+
+    ConnectOutcome::Connected
+}
+
+/// `nmcli device wifi connect` cannot set up 802.1X, the profile is added,
+/// or updated when one is already saved under its name, then brought up.
+fn connect_enterprise(
+    ssid: &str,
+    password: Option<&str>,
+    enterprise: &Enterprise,
+    profile: Option<&str>,
+    interface: &str,
+) -> ConnectOutcome {
+    let (mut setup, mut up) = enterprise_commands(ssid, password, enterprise, profile, interface);
+    match run(&mut setup) {
+        Ok(setup) if setup.status.success() => {}
+        Ok(setup) => {
+            return ConnectOutcome::Other(String::from_utf8_lossy(&setup.stderr).trim().to_string())
+        }
+        Err(e) => return ConnectOutcome::Other(format!("failed to run nmcli: {}", e)),
+    }
+
+    match run(&mut up) {
+        Ok(up) if String::from_utf8_lossy(&up.stdout).contains("successfully activated") => {
+            ConnectOutcome::Connected
+        }
+        Ok(up) => ConnectOutcome::from_nmcli_error(&String::from_utf8_lossy(&up.stderr)),
+        Err(e) => ConnectOutcome::Other(format!("failed to run nmcli: {}", e)),
+    }
+}
+
+/// The `nmcli connection add` or `modify` saving the 802.1X profile, and
+/// the `nmcli connection up` activating it.
+fn enterprise_commands(
+    ssid: &str,
+    password: Option<&str>,
+    enterprise: &Enterprise,
+    profile: Option<&str>,
+    interface: &str,
+) -> (Command, Command) {
+    let profile = profile.unwrap_or(ssid);
+    let saved = known_networks()
+        .map(|known| known.iter().any(|name| name == profile))
+        .unwrap_or(false);
+    let mut nmcli = Command::new("nmcli");
+    if saved {
+        nmcli.args(["connection", "modify", "id", profile]);
+    } else {
+        nmcli.args(["connection", "add", "type", "wifi", "con-name", profile]);
+        nmcli.args(["ifname", interface, "ssid", ssid]);
+    }
+    nmcli.args(["wifi-sec.key-mgmt", "wpa-eap"]);
+    nmcli.args(["802-1x.eap", enterprise.method.name()]);
+    nmcli.args(["802-1x.phase2-auth", "mschapv2"]);
+    nmcli.args(["802-1x.identity", enterprise.identity]);
+    if let Some(password) = password {
+        nmcli.args(["802-1x.password", password]);
+    }
+    if let Some(anonymous_identity) = enterprise.anonymous_identity {
+        nmcli.args(["802-1x.anonymous-identity", anonymous_identity]);
+    }
+    if let Some(ca_cert) = enterprise.ca_cert {
+        nmcli.arg("802-1x.ca-cert").arg(ca_cert);
+    }
+
+    let mut up = Command::new("nmcli");
+    up.args(["connection", "up", "id", profile, "ifname", interface]);
+
+    (nmcli, up)
+}
+
+/// Print what `connect` would do, without touching the connection. The
+/// password a real connect would prompt for is not asked for, `prompted`
+/// says so.
+fn connect_dry_run(
+    ssid: &str,
+    password: Option<&str>,
+    prompted: bool,
+    options: &JoinOptions,
+    interface: &str,
+) -> Result<(), String> {
+    println!("SSID: {}", ssid);
+    println!("Interface: {}", interface);
+    println!(
+        "Password: {}",
+        match password {
+            Some(password) if !password.is_empty() => "********",
+            None if prompted => "prompted for",
+            _ => "none",
+        }
+    );
+    if let Some(bssid) = options.bssid {
+        println!("BSSID: {}", bssid);
+    }
+    if let Some(profile) = options.profile {
+        println!("Profile: {}", profile);
+    }
+
+    let password = password.filter(|password| !password.is_empty());
+    let commands = match &options.enterprise {
+        Some(enterprise) => {
+            let (setup, up) =
+                enterprise_commands(ssid, password, enterprise, options.profile, interface);
+            vec![setup, up]
+        }
+        None => vec![nmcli_connect_command(ssid, password, options, interface)],
+    };
+    for command in &commands {
+        println!("Would run: {}", masked_command(command));
+    }
+
+    Ok(())
+}
+
+/// Flaky access points sometimes only accept the second or third
+/// association, the outcome of the last attempt is returned. Credentials
+/// that can never work are rejected once, before the first attempt.
+fn connect_with_retry(
+    ssid: &str,
+    password: Option<&str>,
+    options: &JoinOptions,
+    interface: &str,
+    attempts: u32,
+    delay: Duration,
+) -> ConnectOutcome {
+    // The WPA passphrase rules say nothing about an 802.1X password.
+    let passphrase = password.filter(|_| options.enterprise.is_none());
+    if let Err(e) = validate_credentials(ssid, passphrase) {
+        return ConnectOutcome::Other(e);
+    }
+
+    let mut attempt = 1;
+    loop {
+        if attempts > 1 {
+            inform!("attempt {}/{}", attempt, attempts);
+        }
+        match connect(ssid, password, options, interface) {
+            ConnectOutcome::Connected => return ConnectOutcome::Connected,
+            outcome if attempt < attempts => {
+                eprintln!("{}", outcome);
+                attempt += 1;
+                thread::sleep(delay);
+            }
+            outcome => return outcome,
+        }
+    }
+}
+
+/// The strongest access point of `ssid` on `band`, when a scan hears the
+/// SSID on more than one band. `None` lets NetworkManager pick.
+fn preferred_bssid(ssid: &str, band: Band, interface: &str) -> Option<String> {
+    let networks = match detect_backend_on(Some(interface.to_string())).scan() {
+        Ok(networks) => networks,
+        Err(e) => {
+            eprintln!("cannot scan for --prefer-band: {}", e);
+            return None;
+        }
+    };
+    let candidates: Vec<&Network> = networks.iter().filter(|n| n.ssid == ssid).collect();
+    let bands: HashSet<&str> = candidates.iter().map(|n| n.band()).collect();
+    if bands.len() < 2 {
+        return None;
+    }
+
+    let preferred = candidates
+        .into_iter()
+        .filter(|n| n.band() == band.name())
+        .max_by(|a, b| signal_sort_key(a).total_cmp(&signal_sort_key(b)))?;
+    inform!("Using {} on {}", preferred.mac, band.name());
+
+    Some(preferred.mac.clone())
+}
+
+/// Check that a scan hears `bssid` broadcasting `ssid`, so a typo in either
+/// is caught before NetworkManager saves a profile pinned to it.
+fn check_bssid(ssid: &str, bssid: &str, interface: &str) -> Result<(), String> {
+    let octets: Vec<&str> = bssid.split(':').collect();
+    if octets.len() != 6
+        || !octets
+            .iter()
+            .all(|o| o.len() == 2 && o.chars().all(|c| c.is_ascii_hexdigit()))
+    {
+        return Err(format!(
+            "{} is not a MAC address like 00:11:22:33:44:55",
+            bssid
+        ));
+    }
+
+    let networks = detect_backend_on(Some(interface.to_string())).scan()?;
+    match networks.iter().find(|n| n.mac.eq_ignore_ascii_case(bssid)) {
+        Some(network) if network.ssid == ssid => Ok(()),
+        Some(network) => Err(format!(
+            "{} broadcasts {}, not {}",
+            bssid, network.ssid, ssid
+        )),
+        None => Err(format!("{} is not in range", bssid)),
+    }
+}
+
+/// Run the `--exec` command of `connect`. The connection already succeeded,
+/// so a failing hook is only reported.
+fn run_hook(command: &str, ssid: &str, interface: &str) {
+    let status = Command::new("sh")
+        .args(["-c", command])
+        .env("IFWIFI_SSID", ssid)
+        .env("IFWIFI_IFACE", interface)
+        .status();
+
+    match status {
+        Ok(status) if status.success() => {}
+        Ok(status) => eprintln!("--exec command failed: {}", status),
+        Err(e) => eprintln!("cannot run --exec command: {}", e),
+    }
+}
+
+/// Join the network `connect` last joined again, nmcli reuses the secrets
+/// saved in its profile.
+fn reconnect() -> Result<(), String> {
+    match LastConnection::load()? {
+        Some(last) => check_outcome(
+            &last.ssid,
+            connect(&last.ssid, None, &JoinOptions::default(), &last.interface),
+        ),
+        None => {
+            inform!("No previous connection recorded, use `ifwifi connect` first");
+            Ok(())
+        }
+    }
+}
+
+fn disconnect(interface: &str) -> Result<(), String> {
+    let nmcli = run(Command::new("nmcli").args(["device", "disconnect", interface]))
+        .map_err(|e| format!("failed to run nmcli: {}", e))?;
+
+    if !nmcli.status.success() {
+        return Err(String::from_utf8_lossy(&nmcli.stderr).trim().to_string());
+    }
+    inform!(
+        "Disconnect Status: {}",
+        String::from_utf8_lossy(&nmcli.stdout).trim()
+    );
+
+    Ok(())
+}
+
+/// Names of the saved wifi profiles, NetworkManager names them after the
+/// SSID unless told otherwise.
+fn known_networks() -> Result<Vec<String>, String> {
+    let nmcli = run(Command::new("nmcli").args(["-t", "-f", "name,type", "connection", "show"]))
+        .map_err(|e| format!("failed to run nmcli: {}", e))?;
+
+    if !nmcli.status.success() {
+        return Err(String::from_utf8_lossy(&nmcli.stderr).trim().to_string());
+    }
+
+    Ok(String::from_utf8_lossy(&nmcli.stdout)
+        .lines()
+        .filter_map(|line| match split_terse(line).as_slice() {
+            [name, kind] if kind == "802-11-wireless" => Some(name.clone()),
+            _ => None,
+        })
+        .collect())
+}
+
+/// The SSIDs with a saved profile, fetched once per scan. Like
+/// `connected_ssids` a missing nmcli only costs the column its marks.
+fn saved_ssids() -> HashSet<String> {
+    match known_networks() {
+        // A profile saved with --profile-name is not named after its SSID.
+        Ok(known) => known
+            .into_iter()
+            .map(|name| profile_ssid(&name).unwrap_or(name))
+            .collect(),
+        Err(e) => {
+            eprintln!("cannot list the saved networks: {}", e);
+            HashSet::new()
+        }
+    }
+}
+
+/// The SSID a saved wifi profile joins, `None` when nmcli cannot tell.
+fn profile_ssid(name: &str) -> Option<String> {
+    let nmcli = run(Command::new("nmcli").args([
+        "-g",
+        "802-11-wireless.ssid",
+        "connection",
+        "show",
+        "id",
+        name,
+    ]))
+    .ok()
+    .filter(|nmcli| nmcli.status.success())?;
+
+    // `-g` escapes a `:` of the SSID like the terse output does.
+    let ssid = split_terse(&String::from_utf8_lossy(&nmcli.stdout)).join(":");
+    Some(ssid).filter(|ssid| !ssid.is_empty())
+}
+
+fn known(in_range: bool) -> Result<(), String> {
+    let known = known_networks()?;
+    if known.is_empty() {
+        println!("No saved wireless networks");
+        return Ok(());
+    }
+
+    let mut known_table = Table::new();
+    known_table.set_format(*format::consts::FORMAT_CLEAN);
+    if in_range {
+        let visible: HashSet<String> = detect_backend()
+            .scan()?
+            .into_iter()
+            .map(|network| network.ssid)
+            .collect();
+        known_table.add_row(row!["Network", "In range"]);
+        for name in known {
+            let ssid = profile_ssid(&name);
+            let seen = if visible.contains(ssid.as_ref().unwrap_or(&name)) {
+                "yes".green()
+            } else {
+                "no".normal()
+            };
+            known_table.add_row(row![name.yellow().bold(), seen]);
+        }
+    } else {
+        known_table.add_row(row!["Network"]);
+        for name in known {
+            known_table.add_row(row![name.yellow().bold()]);
+        }
+    }
+    known_table.printstd();
+
+    Ok(())
+}
+
+fn forget(ssid: &str) -> Result<(), String> {
+    let nmcli = run(Command::new("nmcli").args(["connection", "delete", "id", ssid]))
+        .map_err(|e| format!("failed to run nmcli: {}", e))?;
+
+    // nmcli exits with 10 when the connection does not exist.
+    let stderr = String::from_utf8_lossy(&nmcli.stderr);
+    if nmcli.status.code() == Some(10) || stderr.contains("unknown connection") {
+        return Err(format!("no saved profile named {}", ssid));
+    }
+    if !nmcli.status.success() {
+        return Err(format!("failed to forget {}: {}", ssid, stderr.trim()));
+    }
+    inform!("Forgot {}", ssid);
+
+    Ok(())
+}
+
+/// The `status --json` output.
+#[derive(Debug, Default, Serialize)]
+struct StatusRecord<'a> {
+    connected: bool,
+    ssid: Option<&'a str>,
+    interface: Option<&'a str>,
+    signal_dbm: Option<f32>,
+    quality: Option<SignalMeasure>,
+    /// The first IPv4 address, without its prefix
+    ip: Option<String>,
+}
+
+fn status_json(active: Option<&ActiveConnection>, config: &config::Config) -> Result<(), String> {
+    let record = match active {
+        Some(active) => StatusRecord {
+            connected: true,
+            ssid: Some(&active.ssid),
+            interface: Some(&active.interface),
+            signal_dbm: Some(active.signal).filter(|signal| !signal.is_nan()),
+            quality: Some(active.signal)
+                .filter(|signal| !signal.is_nan())
+                .map(|signal| dBm_signal_measure(signal, &config.thresholds)),
+            ip: interface_addresses(&active.interface)
+                .ok()
+                .and_then(|addresses| addresses.into_iter().next())
+                .map(|address| address.split('/').next().unwrap_or_default().to_string()),
+        },
+        None => StatusRecord::default(),
+    };
+    let output = serde_json::to_string_pretty(&record).map_err(|e| e.to_string())?;
+    println!("{}", output);
+
+    Ok(())
+}
+
+fn status(json: bool, config: &config::Config) -> Result<(), String> {
+    let active = active_connection()?;
+    if json {
+        return status_json(active.as_ref(), config);
+    }
+
+    match active {
+        Some(active) if active.signal.is_nan() => println!(
+            "Connected to {} on {}",
+            active.ssid.yellow().bold(),
+            active.interface
+        ),
+        Some(active) => println!(
+            "Connected to {} on {} ({:.0} dBm, {})",
+            active.ssid.yellow().bold(),
+            active.interface,
+            active.signal,
+            config
+                .labels
+                .label(dBm_signal_measure(active.signal, &config.thresholds))
+        ),
+        None => println!("Not connected"),
+    }
+
+    Ok(())
+}
+
+fn resolve_interface(interface: &Option<String>) -> Result<String, String> {
+    match interface {
+        Some(interface) => Ok(interface.clone()),
+        None => default_wireless_interface().ok_or_else(|| {
+            "no wireless interface detected, please pass --interface explicitly".to_string()
+        }),
+    }
+}
+
+fn interfaces() -> Result<(), String> {
+    let interfaces = wireless_interfaces()?;
+    if interfaces.is_empty() {
+        println!("No wireless interfaces found");
+        return Ok(());
+    }
+
+    let mut interfaces_table = Table::new();
+    interfaces_table.set_format(*format::consts::FORMAT_CLEAN);
+    interfaces_table.add_row(row!["Interface", "State"]);
+
+    for interface in interfaces {
+        let state = operstate(&interface);
+        interfaces_table.add_row(row![interface.yellow().bold(), state]);
+    }
+    interfaces_table.printstd();
+
+    Ok(())
+}
+
+/// The only 2.4GHz channels that do not overlap each other.
+const NON_OVERLAPPING_CHANNELS: [u32; 3] = [1, 6, 11];
+
+#[derive(Debug, Serialize)]
+struct ChannelUsage {
+    #[serde(skip)]
+    channel: u32,
+    #[serde(rename = "count")]
+    networks: usize,
+    #[serde(rename = "strongest_dbm")]
+    strongest_signal: f32,
+    band: &'static str,
+    /// Center frequency in MHz, when it is known
+    #[serde(skip)]
+    frequency: Option<u32>,
+}
+
+fn channel_usage(networks: &[Network]) -> Vec<ChannelUsage> {
+    // The 6GHz channels reuse the 2.4GHz and 5GHz numbers.
+    let mut usage: BTreeMap<(&str, u32), ChannelUsage> = BTreeMap::new();
+    for network in networks.iter().filter(|n| n.channel != 0) {
+        let key = (network.band(), network.channel);
+        let channel = usage.entry(key).or_insert(ChannelUsage {
+            band: network.band(),
+            channel: network.channel,
+            networks: 0,
+            strongest_signal: f32::NAN,
+            frequency: network.center_frequency(),
+        });
+        channel.networks += 1;
+        if channel.strongest_signal.is_nan() || network.signal_dbm > channel.strongest_signal {
+            channel.strongest_signal = network.signal_dbm;
+        }
+    }
+
+    usage.into_values().collect()
+}
+
+/// The non-overlapping 2.4GHz channel sharing the air with the fewest
+/// networks, a 20MHz channel overlaps the four channels on either side.
+fn least_crowded_channel(usage: &[ChannelUsage]) -> Option<(u32, usize)> {
+    let crowding = |candidate: u32| {
+        usage
+            .iter()
+            .filter(|u| u.band == "2.4GHz" && u.channel.abs_diff(candidate) < 5)
+            .map(|u| u.networks)
+            .sum::<usize>()
+    };
+
+    if !usage.iter().any(|u| u.band == "2.4GHz") {
+        return None;
+    }
+    NON_OVERLAPPING_CHANNELS
+        .iter()
+        .map(|&channel| (channel, crowding(channel)))
+        .min_by_key(|&(_, crowding)| crowding)
+}
+
+/// The usage keyed by channel. The 6GHz channels reuse the numbers of the
+/// other bands, their keys are suffixed with "@6GHz" to stay unique.
+fn analyze_json(usage: &[ChannelUsage]) -> Result<(), String> {
+    let mut channels = serde_json::Map::new();
+    for channel in usage {
+        let key = match channel.band {
+            "6GHz" => format!("{}@6GHz", channel.channel),
+            _ => channel.channel.to_string(),
+        };
+        let value = serde_json::to_value(channel).map_err(|e| e.to_string())?;
+        channels.insert(key, value);
+    }
+    let output = serde_json::to_string_pretty(&channels).map_err(|e| e.to_string())?;
+    println!("{}", output);
+
+    Ok(())
+}
+
+fn analyze(json: bool) -> Result<(), String> {
+    let networks = detect_backend().scan()?;
+    let usage = channel_usage(&networks);
+    if json {
+        return analyze_json(&usage);
+    }
+    if usage.is_empty() {
+        println!("No networks found");
+        return Ok(());
+    }
+
+    let domain = regulatory_domain();
+    let mut analyze_table = Table::new();
+    analyze_table.set_format(*format::consts::FORMAT_CLEAN);
+    analyze_table.add_row(row!["Band", "Channel", "Networks", "Strongest", ""]);
+
+    for channel in &usage {
+        let outside = domain
+            .as_ref()
+            .filter(|domain| channel.frequency.is_some_and(|f| !domain.allows(f)));
+        let note = if let Some(domain) = outside {
+            format!("not allowed in {}", domain.country).red()
+        } else if channel.band == "2.4GHz" && !NON_OVERLAPPING_CHANNELS.contains(&channel.channel) {
+            "overlapping".yellow()
+        } else {
+            "".normal()
+        };
+        let strongest = if channel.strongest_signal.is_nan() {
+            "?".to_string()
+        } else {
+            format!("{:.2} dBm", channel.strongest_signal)
+        };
+        analyze_table.add_row(row![
+            channel.band,
+            channel.channel.to_string().yellow().bold(),
+            channel.networks,
+            strongest,
+            note
+        ]);
+    }
+    analyze_table.printstd();
+
+    if let Some((channel, crowding)) = least_crowded_channel(&usage) {
+        println!(
+            "Least crowded 2.4GHz channel: {} ({} networks overlapping it)",
+            channel.to_string().green().bold(),
+            crowding
+        );
+    }
+
+    Ok(())
+}
+
+fn doctor_check(label: &str, ok: bool, hint: &str) {
+    if ok {
+        println!("[{}] {}", "ok".green().bold(), label);
+    } else {
+        println!("[{}] {} ({})", "missing".red().bold(), label, hint);
+    }
+}
+
+fn doctor() -> Result<(), String> {
+    let nmcli = command_exists("nmcli");
+    let iw = command_exists("iw");
+    let interfaces = wireless_interfaces().unwrap_or_default();
+
+    doctor_check(
+        "nmcli",
+        nmcli,
+        "install NetworkManager to connect and to scan",
+    );
+    doctor_check("iw", iw, "install iw to scan without NetworkManager");
+    doctor_check(
+        format!("wireless interface {}", interfaces.join(", ")).trim_end(),
+        !interfaces.is_empty(),
+        "no interface under /sys/class/net has a wireless directory",
+    );
+
+    if !nmcli && !iw {
+        return Err("no usable scan backend, install nmcli or iw".to_string());
+    }
+
+    Ok(())
+}
+
+fn rfkill(args: &[&str]) -> Result<String, String> {
+    let rfkill = find_command("rfkill").ok_or_else(|| "rfkill is not installed".to_string())?;
+    let output =
+        run(Command::new(rfkill).args(args)).map_err(|e| format!("failed to run rfkill: {}", e))?;
+
+    if !output.status.success() {
+        return Err(String::from_utf8_lossy(&output.stderr).trim().to_string());
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// The BSSIDs that appeared, disappeared or whose signal moved by at least
+/// `threshold` dB between two saved scans, matched by MAC.
+fn diff(old: &Path, new: &Path, threshold: f32) -> Result<(), String> {
+    let old = load_scan(old)?;
+    let new = load_scan(new)?;
+    let before: HashMap<&str, &Network> = old.iter().map(|n| (n.mac.as_str(), n)).collect();
+    let after: HashMap<&str, &Network> = new.iter().map(|n| (n.mac.as_str(), n)).collect();
+    let (mut added, mut removed, mut changed) = (0, 0, 0);
+
+    for network in &new {
+        match before.get(network.mac.as_str()) {
+            None => {
+                added += 1;
+                println!(
+                    "{} {} {} {:.2} dBm",
+                    "+".green().bold(),
+                    network.mac,
+                    network.ssid.yellow().bold(),
+                    network.signal_dbm
+                );
+            }
+            Some(previous) if (network.signal_dbm - previous.signal_dbm).abs() >= threshold => {
+                changed += 1;
+                println!(
+                    "{} {} {} {:.2} -> {:.2} dBm ({:+.2})",
+                    "~".yellow().bold(),
+                    network.mac,
+                    network.ssid.yellow().bold(),
+                    previous.signal_dbm,
+                    network.signal_dbm,
+                    network.signal_dbm - previous.signal_dbm
+                );
+            }
+            Some(_) => {}
+        }
+    }
+    for network in old.iter().filter(|n| !after.contains_key(n.mac.as_str())) {
+        removed += 1;
+        println!(
+            "{} {} {}",
+            "-".red().bold(),
+            network.mac,
+            network.ssid.yellow().bold()
+        );
+    }
+    inform!("{} added, {} removed, {} changed", added, removed, changed);
+
+    Ok(())
+}
+
+fn link(action: LinkAction, interface: &str) -> Result<(), String> {
+    if !is_wireless(interface) {
+        return Err(format!("{} is not a wireless interface", interface));
+    }
+    let action = match action {
+        LinkAction::Up => "up",
+        LinkAction::Down => "down",
+    };
+    let ip = find_command("ip").ok_or_else(|| "ip is not installed".to_string())?;
+    let output = run(Command::new(ip).args(["link", "set", interface, action]))
+        .map_err(|e| format!("failed to run ip: {}", e))?;
+
+    if !output.status.success() {
+        return Err(String::from_utf8_lossy(&output.stderr).trim().to_string());
+    }
+    inform!("{}: {}", interface.yellow().bold(), operstate(interface));
+
+    Ok(())
+}
+
+fn radio(state: RadioState) -> Result<(), String> {
+    let action = match state {
+        RadioState::On => "unblock",
+        RadioState::Off => "block",
+    };
+    rfkill(&[action, "wifi"])?;
+
+    // Every radio is listed by rfkill as an "N: phyN: Wireless LAN" line
+    // followed by its indented "Soft blocked: yes|no" and "Hard blocked"
+    // states.
+    for line in rfkill(&["list", "wifi"])?.lines() {
+        let line = line.trim();
+        if let Some(radio) = line.strip_suffix(": Wireless LAN") {
+            println!("{}", radio.yellow().bold());
+        } else if let Some((kind, blocked)) = line.split_once(" blocked: ") {
+            let blocked = match blocked {
+                "yes" => blocked.red(),
+                _ => blocked.green(),
+            };
+            println!("  {} blocked: {}", kind, blocked);
+        }
+    }
+
+    Ok(())
+}
+
+/// Backslash the characters the `WIFI:` QR code format reserves.
+fn qr_escape(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        if matches!(c, '\\' | ';' | ',' | ':' | '"') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+
+    escaped
+}
+
+fn wifi_qr_payload(
+    ssid: &str,
+    password: Option<&str>,
+    security: QrSecurity,
+) -> Result<String, String> {
+    let security = match security {
+        QrSecurity::Wpa => "WPA",
+        QrSecurity::Wep => "WEP",
+        QrSecurity::Nopass => return Ok(format!("WIFI:T:nopass;S:{};;", qr_escape(ssid))),
+    };
+    let password = password.ok_or_else(|| {
+        format!(
+            "a --password is required for {}, use --security nopass for open networks",
+            security
+        )
+    })?;
+
+    Ok(format!(
+        "WIFI:T:{};S:{};P:{};;",
+        security,
+        qr_escape(ssid),
+        qr_escape(password)
+    ))
+}
+
+fn qr(ssid: &str, password: Option<&str>, security: QrSecurity) -> Result<(), String> {
+    let payload = wifi_qr_payload(ssid, password, security)?;
+    let code =
+        QrCode::new(payload.as_bytes()).map_err(|e| format!("cannot build QR code: {}", e))?;
+    let image = code
+        .render::<unicode::Dense1x2>()
+        .dark_color(unicode::Dense1x2::Light)
+        .light_color(unicode::Dense1x2::Dark)
+        .build();
+    println!("{}", image);
+
+    Ok(())
+}
+
+fn main() -> Result<(), String> {
+    let cli = Cli::parse();
+    colored::control::set_override(!cli.no_color && cli.color.enabled());
+    BLINK.store(!cli.no_blink, Ordering::Relaxed);
+    set_verbose(cli.verbose);
+    QUIET.store(cli.quiet, Ordering::Relaxed);
+    let config = config::Config::load()?;
+
+    match &cli.command {
+        Some(Commands::Scan(args)) => {
+            // Reading a saved scan touches no radio, it needs no root.
+            if args.load.is_none() && !is_root() {
+                exit(2);
+            }
+            scan(args, &config)
+        }
+        Some(Commands::Connect(args)) => {
+            // A dry run changes nothing, it needs no root.
+            if !args.dry_run && !is_root() {
+                exit(2);
+            }
+            let interface = resolve_interface(&args.interface)?;
+            let password = match (&args.password, &args.password_file) {
+                (Some(password), _) if password == "-" => Some(read_password_stdin()?),
+                (Some(password), _) => Some(password.clone()),
+                (None, Some(path)) => Some(read_password_file(path)?),
+                (None, None) if args.open => None,
+                // Nothing is joined, there is no need to ask.
+                (None, None) if args.dry_run => None,
+                #[cfg(feature = "keyring")]
+                (None, None) if args.from_keyring => Some(secrets::load_password(&args.ssid)?),
+                (None, None) => Some(read_password(&args.ssid)?),
+            };
+            // An empty password joins the network as an open one.
+            if password.as_deref().unwrap_or_default().is_empty()
+                && !args.yes
+                && !args.enterprise
+                && !args.dry_run
+            {
+                confirm_open(&args.ssid)?;
+            }
+            if let Some(bssid) = &args.bssid {
+                check_bssid(&args.ssid, bssid, &interface)?;
+            }
+            let bssid = args.bssid.clone().or_else(|| {
+                args.prefer_band
+                    .and_then(|band| preferred_bssid(&args.ssid, band, &interface))
+            });
+            let options = JoinOptions {
+                bssid: bssid.as_deref(),
+                profile: args.profile_name.as_deref(),
+                enterprise: args.enterprise(),
+            };
+            if args.dry_run {
+                let prompted = password.is_none() && !args.open;
+                return connect_dry_run(
+                    &args.ssid,
+                    password.as_deref(),
+                    prompted,
+                    &options,
+                    &interface,
+                );
+            }
+            let outcome = connect_with_retry(
+                &args.ssid,
+                password.as_deref(),
+                &options,
+                &interface,
+                args.retry,
+                Duration::from_secs(args.retry_delay),
+            );
+            check_outcome(&args.ssid, outcome)?;
+            #[cfg(feature = "keyring")]
+            if let Some(password) = password.as_deref().filter(|p| !p.is_empty()) {
+                if let Err(e) = secrets::store_password(&args.ssid, password) {
+                    eprintln!("cannot save the password in the keyring: {}", e);
+                }
+            }
+            if args.verify {
+                let timeout = Duration::from_secs(args.verify_timeout);
+                check_outcome(
+                    &args.ssid,
+                    verify_connection(&args.ssid, &interface, timeout),
+                )?;
+            }
+            report_lease(&interface);
+            if let Some(command) = &args.exec {
+                run_hook(command, &args.ssid, &interface);
+            }
+            Ok(())
+        }
+        Some(Commands::Reconnect {}) => {
+            if !is_root() {
+                exit(2);
+            }
+            reconnect()
+        }
+        Some(Commands::Disconnect { interface }) => {
+            if !is_root() {
+                exit(2);
+            }
+            disconnect(&resolve_interface(interface)?)
+        }
+        Some(Commands::Known { in_range }) => known(*in_range),
+        Some(Commands::Forget { ssid }) => {
+            if !is_root() {
+                exit(2);
+            }
+            forget(ssid)
+        }
+        Some(Commands::Status { json }) => status(*json, &config),
+        Some(Commands::Interfaces {}) => interfaces(),
+        Some(Commands::Analyze { json }) => analyze(*json),
+        Some(Commands::Doctor {}) => doctor(),
+        Some(Commands::Radio { state }) => {
+            if !is_root() {
+                exit(2);
+            }
+            radio(*state)
+        }
+        Some(Commands::Diff {
+            old,
+            new,
+            threshold,
+        }) => diff(old, new, *threshold),
+        Some(Commands::Link { action, interface }) => {
+            if !is_root() {
+                exit(2);
+            }
+            link(*action, interface)
+        }
+        Some(Commands::Qr {
+            ssid,
+            password,
+            security,
+        }) => qr(ssid, password.as_deref(), *security),
         // e.g. `$ cli completions bash`
         Some(Commands::Completions { shell }) => {
             shell.generate(&mut Cli::command(), &mut std::io::stdout());
@@ -240,3 +2990,124 @@ fn main() -> Result<(), String> {
         None => Ok(()),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scan_args(argv: &[&str]) -> ScanArgs {
+        match Cli::parse_from(argv).command {
+            Some(Commands::Scan(args)) => args,
+            _ => unreachable!("not a scan command line"),
+        }
+    }
+
+    #[test]
+    fn utf8_ssid_cell() {
+        colored::control::set_override(false);
+        let network = Network::from(wifiscanner::Wifi {
+            mac: "aa:bb:cc:dd:ee:ff".to_string(),
+            ssid: "ClemenciaBoh\\xc3\\xb3rquez".to_string(),
+            channel: "6".to_string(),
+            signal_level: "-60".to_string(),
+            security: String::new(),
+        });
+        let args = scan_args(&["ifwifi", "scan"]);
+        let config = config::Config::default();
+
+        let row = scan_table_format(&network, &HashSet::new(), None, None, None, &args, &config);
+        let column = args
+            .table_fields()
+            .iter()
+            .position(|field| *field == Field::Ssid)
+            .unwrap();
+        let cell = row.get_cell(column).unwrap().get_content();
+
+        assert_eq!(cell, "ClemenciaBohórquez");
+        assert!(cell.contains('ó'));
+    }
+
+    #[test]
+    fn active_ssid_with_colon() {
+        let active = parse_active_ssids("yes:My\\:Network\n");
+
+        assert!(active.contains("My:Network"));
+    }
+
+    #[test]
+    fn active_ssid_with_backslash() {
+        let active = parse_active_ssids("no:Other\nyes:Back\\\\slash\n");
+
+        assert!(active.contains("Back\\slash"));
+        assert!(!active.contains("Other"));
+    }
+
+    #[test]
+    fn active_ssid_exact_match() {
+        let active = parse_active_ssids("yes:Home2\nno:Home\n");
+
+        assert!(active.contains("Home2"));
+        assert!(!active.contains("Home"));
+    }
+
+    #[test]
+    fn active_ssid_not_a_prefix() {
+        let active = parse_active_ssids("yes:Home\nno:Home2\n");
+
+        assert!(active.contains("Home"));
+        assert!(!active.contains("Home2"));
+    }
+
+    #[test]
+    fn active_hidden_ssid_ignored() {
+        assert!(parse_active_ssids("yes:\n").is_empty());
+    }
+
+    #[test]
+    fn requested_fields_keep_their_columns() {
+        let network = Network {
+            mac: "aa:bb:cc:dd:ee:ff".to_string(),
+            ssid: "Home".to_string(),
+            ..Network::default()
+        };
+        let fields = [Field::Mac, Field::Interface, Field::Ssid];
+        let values = network_values(&[network], &fields, None, &config::Config::default()).unwrap();
+
+        let keys: Vec<&str> = values[0].keys().map(String::as_str).collect();
+        assert_eq!(keys, ["mac", "interface", "ssid"]);
+        assert!(values[0]["interface"].is_null());
+    }
+
+    #[test]
+    fn wep_keys_accepted() {
+        for key in [
+            "abcde",
+            "abcdefghijklm",
+            "0123456789",
+            "0123456789abcdef0123456789",
+        ] {
+            assert!(validate_credentials("Old", Some(key)).is_ok(), "{}", key);
+        }
+        assert!(validate_credentials("Home", Some("sixsix")).is_err());
+        assert!(validate_credentials("Home", Some("0123456789abcdefgh")).is_ok());
+    }
+
+    #[test]
+    fn summary_counts_unknown_security_apart() {
+        let network = |security| Network {
+            channel: 6,
+            security,
+            ..Network::default()
+        };
+        let networks = vec![
+            network(Security::Wpa2Psk),
+            network(Security::Open),
+            network(Security::Unknown(String::new())),
+        ];
+
+        assert_eq!(
+            scan_summary(&networks),
+            "Found 3 networks (1 secured, 1 open, 1 unknown) across 2.4GHz"
+        );
+    }
+}
@@ -0,0 +1,119 @@
+/*-
+ * SPDX-License-Identifier: BSD-2-Clause
+ *
+ * BSD 2-Clause License
+ *
+ * Copyright (c) 2021, Marcelo Araujo <araujobsdport@gmail.com>
+ * All rights reserved.
+ *
+ * Redistribution and use in source and binary forms, with or without
+ * modification, are permitted provided that the following conditions are met:
+ *
+ * 1. Redistributions of source code must retain the above copyright notice, this
+ *    list of conditions and the following disclaimer.
+ *
+ * 2. Redistributions in binary form must reproduce the above copyright notice,
+ *    this list of conditions and the following disclaimer in the documentation
+ *    and/or other materials provided with the distribution.
+ *
+ * THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS"
+ * AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE
+ * IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE ARE
+ * DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT HOLDER OR CONTRIBUTORS BE LIABLE
+ * FOR ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL, EXEMPLARY, OR CONSEQUENTIAL
+ * DAMAGES (INCLUDING, BUT NOT LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR
+ * SERVICES; LOSS OF USE, DATA, OR PROFITS; OR BUSINESS INTERRUPTION) HOWEVER
+ * CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY,
+ * OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
+ * OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+ */
+
+//! A small compiled-in subset of the IEEE OUI registry, covering the
+//! vendors whose access points and routers are most commonly seen.
+
+/// Sorted by prefix so it can be binary searched.
+const VENDORS: &[(&str, &str)] = &[
+    ("00:00:0C", "Cisco"),
+    ("00:03:93", "Apple"),
+    ("00:04:0E", "AVM"),
+    ("00:0B:85", "Cisco"),
+    ("00:0B:86", "Aruba"),
+    ("00:0C:42", "MikroTik"),
+    ("00:10:18", "Broadcom"),
+    ("00:11:95", "D-Link"),
+    ("00:12:17", "Linksys"),
+    ("00:13:10", "Linksys"),
+    ("00:13:49", "Zyxel"),
+    ("00:14:6C", "Netgear"),
+    ("00:14:BF", "Linksys"),
+    ("00:15:6D", "Ubiquiti"),
+    ("00:18:0A", "Cisco Meraki"),
+    ("00:18:39", "Linksys"),
+    ("00:18:82", "Huawei"),
+    ("00:1A:11", "Google"),
+    ("00:1A:1E", "Aruba"),
+    ("00:1A:70", "Linksys"),
+    ("00:1B:11", "D-Link"),
+    ("00:1B:21", "Intel"),
+    ("00:1B:2F", "Netgear"),
+    ("00:1C:10", "Linksys"),
+    ("00:1C:F0", "D-Link"),
+    ("00:1D:0F", "TP-Link"),
+    ("00:1D:7E", "Linksys"),
+    ("00:1E:2A", "Netgear"),
+    ("00:1E:52", "Apple"),
+    ("00:1E:58", "D-Link"),
+    ("00:1E:8C", "ASUS"),
+    ("00:1F:33", "Netgear"),
+    ("00:21:91", "D-Link"),
+    ("00:22:15", "ASUS"),
+    ("00:22:6B", "Linksys"),
+    ("00:24:01", "D-Link"),
+    ("00:24:8C", "ASUS"),
+    ("00:24:B2", "Netgear"),
+    ("00:25:9C", "Linksys"),
+    ("00:26:18", "ASUS"),
+    ("00:26:5A", "D-Link"),
+    ("00:27:22", "Ubiquiti"),
+    ("00:40:96", "Cisco"),
+    ("00:50:F2", "Microsoft"),
+    ("00:A0:C5", "Zyxel"),
+    ("00:E0:4C", "Realtek"),
+    ("00:E0:FC", "Huawei"),
+    ("04:18:D6", "Ubiquiti"),
+    ("1C:7E:E5", "D-Link"),
+    ("24:0A:C4", "Espressif"),
+    ("24:A4:3C", "Ubiquiti"),
+    ("30:AE:A4", "Espressif"),
+    ("4C:5E:0C", "MikroTik"),
+    ("50:C7:BF", "TP-Link"),
+    ("64:09:80", "Xiaomi"),
+    ("84:F3:EB", "Espressif"),
+    ("A0:04:60", "Netgear"),
+    ("B8:27:EB", "Raspberry Pi"),
+    ("BC:05:43", "AVM"),
+    ("C0:25:06", "AVM"),
+    ("C0:56:27", "Belkin"),
+    ("C8:3A:35", "Tenda"),
+    ("DC:A6:32", "Raspberry Pi"),
+    ("F4:F2:6D", "TP-Link"),
+    ("F4:F5:D8", "Google"),
+];
+
+/// The manufacturer owning the first three octets of `mac`, backends
+/// print them in either case and with `:` or `-` separators.
+pub fn vendor(mac: &str) -> Option<&'static str> {
+    let prefix: String = mac
+        .get(..8)?
+        .chars()
+        .map(|c| match c {
+            '-' => ':',
+            c => c.to_ascii_uppercase(),
+        })
+        .collect();
+
+    VENDORS
+        .binary_search_by(|(oui, _)| (*oui).cmp(prefix.as_str()))
+        .ok()
+        .map(|i| VENDORS[i].1)
+}
@@ -0,0 +1,103 @@
+/*-
+ * SPDX-License-Identifier: BSD-2-Clause
+ *
+ * BSD 2-Clause License
+ *
+ * Copyright (c) 2021, Marcelo Araujo <araujobsdport@gmail.com>
+ * All rights reserved.
+ *
+ * Redistribution and use in source and binary forms, with or without
+ * modification, are permitted provided that the following conditions are met:
+ *
+ * 1. Redistributions of source code must retain the above copyright notice, this
+ *    list of conditions and the following disclaimer.
+ *
+ * 2. Redistributions in binary form must reproduce the above copyright notice,
+ *    this list of conditions and the following disclaimer in the documentation
+ *    and/or other materials provided with the distribution.
+ *
+ * THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS"
+ * AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE
+ * IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE ARE
+ * DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT HOLDER OR CONTRIBUTORS BE LIABLE
+ * FOR ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL, EXEMPLARY, OR CONSEQUENTIAL
+ * DAMAGES (INCLUDING, BUT NOT LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR
+ * SERVICES; LOSS OF USE, DATA, OR PROFITS; OR BUSINESS INTERRUPTION) HOWEVER
+ * CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY,
+ * OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
+ * OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+ */
+
+//! The frequency ranges the regulatory domain of the system allows, as
+//! reported by `iw reg get`.
+
+use crate::backend::{find_command, run};
+use std::process::Command;
+
+/// Half the width of a 20MHz channel, the whole channel has to fit in a
+/// range.
+const HALF_CHANNEL_WIDTH: f32 = 10.0;
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct RegulatoryDomain {
+    /// ISO 3166 country code, "00" for the world domain
+    pub country: String,
+    /// Allowed (start, end) frequency ranges in MHz
+    pub ranges: Vec<(f32, f32)>,
+}
+
+impl RegulatoryDomain {
+    /// Whether a 20MHz channel centered on `frequency` fits in one of the
+    /// allowed ranges.
+    pub fn allows(&self, frequency: u32) -> bool {
+        let frequency = frequency as f32;
+        self.ranges.iter().any(|&(start, end)| {
+            start <= frequency - HALF_CHANNEL_WIDTH && frequency + HALF_CHANNEL_WIDTH <= end
+        })
+    }
+}
+
+/// The domain of `iw reg get`, `None` when iw is missing or its output
+/// cannot be read.
+pub fn regulatory_domain() -> Option<RegulatoryDomain> {
+    let iw = find_command("iw")?;
+    let output = run(Command::new(iw).args(["reg", "get"])).ok()?;
+    if !output.status.success() {
+        return None;
+    }
+
+    parse_iw_reg(&String::from_utf8_lossy(&output.stdout))
+}
+
+/// Only the first domain is read, the "global" one, the self-managed
+/// radios listed after it under their "phy#N" have their own.
+fn parse_iw_reg(output: &str) -> Option<RegulatoryDomain> {
+    let mut domain: Option<RegulatoryDomain> = None;
+
+    for line in output.lines() {
+        let line = line.trim();
+        if let Some(rest) = line.strip_prefix("country ") {
+            if domain.is_some() {
+                break;
+            }
+            let country = rest.split(':').next().unwrap_or_default();
+            domain = Some(RegulatoryDomain {
+                country: country.to_string(),
+                ranges: Vec::new(),
+            });
+        } else if let Some(domain) = domain.as_mut() {
+            // e.g. "(2402 - 2472 @ 40), (N/A, 30), (N/A)"
+            let range = line
+                .strip_prefix('(')
+                .and_then(|rest| rest.split(" @").next())
+                .and_then(|range| range.split_once(" - "));
+            if let Some((start, end)) = range {
+                if let (Ok(start), Ok(end)) = (start.trim().parse(), end.trim().parse()) {
+                    domain.ranges.push((start, end));
+                }
+            }
+        }
+    }
+
+    domain
+}
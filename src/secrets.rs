@@ -0,0 +1,60 @@
+/*-
+ * SPDX-License-Identifier: BSD-2-Clause
+ *
+ * BSD 2-Clause License
+ *
+ * Copyright (c) 2021, Marcelo Araujo <araujobsdport@gmail.com>
+ * All rights reserved.
+ *
+ * Redistribution and use in source and binary forms, with or without
+ * modification, are permitted provided that the following conditions are met:
+ *
+ * 1. Redistributions of source code must retain the above copyright notice, this
+ *    list of conditions and the following disclaimer.
+ *
+ * 2. Redistributions in binary form must reproduce the above copyright notice,
+ *    this list of conditions and the following disclaimer in the documentation
+ *    and/or other materials provided with the distribution.
+ *
+ * THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS"
+ * AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE
+ * IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE ARE
+ * DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT HOLDER OR CONTRIBUTORS BE LIABLE
+ * FOR ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL, EXEMPLARY, OR CONSEQUENTIAL
+ * DAMAGES (INCLUDING, BUT NOT LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR
+ * SERVICES; LOSS OF USE, DATA, OR PROFITS; OR BUSINESS INTERRUPTION) HOWEVER
+ * CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY,
+ * OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
+ * OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+ */
+
+//! The passwords of `connect`, kept in the system secret service under
+//! an `ifwifi:<ssid>` entry.
+
+use keyring::Entry;
+
+fn entry(ssid: &str) -> Result<Entry, String> {
+    Entry::new(&format!("ifwifi:{}", ssid), ssid).map_err(keyring_error)
+}
+
+fn keyring_error(e: keyring::Error) -> String {
+    match e {
+        keyring::Error::NoStorageAccess(e) | keyring::Error::PlatformFailure(e) => {
+            format!("no keyring available: {}", e)
+        }
+        e => e.to_string(),
+    }
+}
+
+/// The password saved for `ssid` by an earlier `connect`.
+pub fn load_password(ssid: &str) -> Result<String, String> {
+    match entry(ssid)?.get_password() {
+        Ok(password) => Ok(password),
+        Err(keyring::Error::NoEntry) => Err(format!("no password for {} in the keyring", ssid)),
+        Err(e) => Err(keyring_error(e)),
+    }
+}
+
+pub fn store_password(ssid: &str, password: &str) -> Result<(), String> {
+    entry(ssid)?.set_password(password).map_err(keyring_error)
+}
@@ -0,0 +1,107 @@
+/*-
+ * SPDX-License-Identifier: BSD-2-Clause
+ *
+ * BSD 2-Clause License
+ *
+ * Copyright (c) 2021, Marcelo Araujo <araujobsdport@gmail.com>
+ * All rights reserved.
+ *
+ * Redistribution and use in source and binary forms, with or without
+ * modification, are permitted provided that the following conditions are met:
+ *
+ * 1. Redistributions of source code must retain the above copyright notice, this
+ *    list of conditions and the following disclaimer.
+ *
+ * 2. Redistributions in binary form must reproduce the above copyright notice,
+ *    this list of conditions and the following disclaimer in the documentation
+ *    and/or other materials provided with the distribution.
+ *
+ * THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS"
+ * AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE
+ * IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE ARE
+ * DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT HOLDER OR CONTRIBUTORS BE LIABLE
+ * FOR ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL, EXEMPLARY, OR CONSEQUENTIAL
+ * DAMAGES (INCLUDING, BUT NOT LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR
+ * SERVICES; LOSS OF USE, DATA, OR PROFITS; OR BUSINESS INTERRUPTION) HOWEVER
+ * CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY,
+ * OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
+ * OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+ */
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+/// The network `connect` last joined, kept in
+/// `~/.local/state/ifwifi/last.json` for `reconnect`. The password stays in
+/// the NetworkManager profile, it is never written here.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct LastConnection {
+    pub ssid: String,
+    pub interface: String,
+}
+
+impl LastConnection {
+    /// `None` when nothing was recorded yet.
+    pub fn load() -> Result<Option<LastConnection>, String> {
+        read_state("last.json")
+    }
+
+    pub fn save(&self) -> Result<(), String> {
+        write_state("last.json", self)
+    }
+}
+
+/// The signal of every BSSID seen by the previous `scan --delta`, kept in
+/// `~/.local/state/ifwifi/signals.json`.
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct SignalHistory {
+    pub signals: HashMap<String, f32>,
+}
+
+impl SignalHistory {
+    /// Empty when no scan was recorded yet.
+    pub fn load() -> Result<SignalHistory, String> {
+        Ok(read_state("signals.json")?.unwrap_or_default())
+    }
+
+    pub fn save(&self) -> Result<(), String> {
+        write_state("signals.json", self)
+    }
+}
+
+fn read_state<T: DeserializeOwned>(name: &str) -> Result<Option<T>, String> {
+    let path = match state_path(name) {
+        Some(path) => path,
+        None => return Ok(None),
+    };
+
+    match fs::read_to_string(&path) {
+        Ok(contents) => serde_json::from_str(&contents)
+            .map(Some)
+            .map_err(|e| format!("invalid state {}: {}", path.display(), e)),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(format!("cannot read state {}: {}", path.display(), e)),
+    }
+}
+
+fn write_state<T: Serialize>(name: &str, state: &T) -> Result<(), String> {
+    let path = state_path(name).ok_or_else(|| "cannot locate the home directory".to_string())?;
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(|e| format!("cannot create {}: {}", dir.display(), e))?;
+    }
+    let contents = serde_json::to_string(state).map_err(|e| e.to_string())?;
+
+    fs::write(&path, contents).map_err(|e| format!("cannot write {}: {}", path.display(), e))
+}
+
+fn state_path(name: &str) -> Option<PathBuf> {
+    let state_home = env::var_os("XDG_STATE_HOME")
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".local/state")))?;
+
+    Some(state_home.join("ifwifi").join(name))
+}